@@ -0,0 +1,468 @@
+use crate::brainvision::{write_brainvision, BinaryFormat};
+use crate::easy_reader::Float;
+use crate::edf::{self, EdfOptions};
+use crate::error::{EasyError, Result};
+use crate::recording::Recording;
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Version of the BIDS specification the datasets follow.
+const BIDS_VERSION: &str = "1.9.0";
+
+/// Data format of the recordings of a BIDS dataset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BidsFormat {
+    /// BrainVision triplet with 32-bit float samples.
+    BrainVision,
+    /// EDF+ with 16-bit samples.
+    Edf,
+    /// BDF+ with 24-bit samples, accelerometer and status channels.
+    Bdf,
+}
+
+/// Subject, session, task and run labels of a recording.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BidsEntities {
+    pub subject: String,
+    pub session: Option<String>,
+    pub task: String,
+    pub run: Option<u32>,
+}
+
+impl BidsEntities {
+    /// Creates the labels of a single-session, single-run recording.
+    pub fn new(subject: &str, task: &str) -> Self {
+        BidsEntities {
+            subject: subject.to_string(),
+            session: None,
+            task: task.to_string(),
+            run: None,
+        }
+    }
+
+    /// Returns the subject and session part of the file names, e.g. `sub-01_ses-02`.
+    fn session_prefix(&self) -> String {
+        match &self.session {
+            Some(session) => format!("sub-{}_ses-{}", self.subject, session),
+            None => format!("sub-{}", self.subject),
+        }
+    }
+
+    /// Returns the file name prefix of the recording, e.g. `sub-01_ses-02_task-rest_run-1`.
+    fn prefix(&self) -> String {
+        let mut prefix = format!("{}_task-{}", self.session_prefix(), self.task);
+        if let Some(run) = self.run {
+            prefix.push_str(&format!("_run-{}", run));
+        }
+        prefix
+    }
+
+    /// Returns the `eeg` directory of the recording, relative to the dataset root.
+    fn directory(&self) -> PathBuf {
+        let mut directory = PathBuf::from(format!("sub-{}", self.subject));
+        if let Some(session) = &self.session {
+            directory.push(format!("ses-{}", session));
+        }
+        directory.join("eeg")
+    }
+
+    /// Checks that the labels are alphanumeric, as BIDS requires.
+    fn check(&self) -> Result<()> {
+        let labels = [Some(&self.subject), self.session.as_ref(), Some(&self.task)];
+        for label in labels.into_iter().flatten() {
+            if label.is_empty() || !label.chars().all(|c| c.is_ascii_alphanumeric()) {
+                return Err(EasyError::Unsupported(format!(
+                    "BIDS labels must be alphanumeric, got {:?}",
+                    label
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Electrode positions, written to `_electrodes.tsv` and `_coordsystem.json`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElectrodePositions {
+    /// Name of the coordinate system, e.g. `CapTrak` or `EEGLAB`.
+    pub coordinate_system: String,
+    /// Unit of the coordinates, `m`, `cm` or `mm`.
+    pub units: String,
+    /// Position of each electrode, by name.
+    pub positions: HashMap<String, [Float; 3]>,
+}
+
+/// Settings of a BIDS export.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BidsOptions {
+    /// Name of the dataset in `dataset_description.json`.
+    pub dataset_name: String,
+    /// Format of the data files.
+    pub format: BidsFormat,
+    /// Frequency of the power line, in Hz, written as `n/a` when unknown.
+    pub power_line_frequency: Option<Float>,
+    /// Positions of the electrodes, if they were measured or taken from a template.
+    pub electrode_positions: Option<ElectrodePositions>,
+}
+
+impl Default for BidsOptions {
+    fn default() -> Self {
+        BidsOptions {
+            dataset_name: "NIC recordings".to_string(),
+            format: BidsFormat::BrainVision,
+            power_line_frequency: None,
+            electrode_positions: None,
+        }
+    }
+}
+
+/// Writes recordings as a BIDS-EEG dataset rooted at `root`.
+///
+/// Writes `dataset_description.json` and `participants.tsv` at the root, then each recording
+/// with [`write_bids_recording`].
+pub fn write_bids(
+    root: &str,
+    recordings: &[(&Recording, BidsEntities)],
+    options: &BidsOptions,
+) -> Result<()> {
+    for (recording, entities) in recordings {
+        write_bids_recording(root, recording, entities, options)?;
+    }
+    let root = Path::new(root);
+    fs::create_dir_all(root)?;
+
+    write_json(
+        &root.join("dataset_description.json"),
+        &json!({
+            "Name": options.dataset_name,
+            "BIDSVersion": BIDS_VERSION,
+            "DatasetType": "raw",
+            "GeneratedBy": [{ "Name": "easy-rs", "Version": env!("CARGO_PKG_VERSION") }],
+        }),
+    )?;
+
+    // Keep the participants of earlier exports to the same dataset
+    let participants_path = root.join("participants.tsv");
+    let mut participants: BTreeSet<String> = match fs::read_to_string(&participants_path) {
+        Ok(content) => content
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split('\t').next())
+            .map(str::to_string)
+            .collect(),
+        Err(_) => BTreeSet::new(),
+    };
+    participants.extend(
+        recordings
+            .iter()
+            .map(|(_, entities)| format!("sub-{}", entities.subject)),
+    );
+    let mut rows = vec!["participant_id".to_string()];
+    rows.extend(participants);
+    write_tsv(&participants_path, &rows)
+}
+
+/// Writes a recording into the `eeg` directory of its subject and session.
+///
+/// Besides the data file, writes the `_eeg.json` sidecar (sampling rate, `.info` filter
+/// settings and device information), `_channels.tsv`, `_events.tsv` with one row per event,
+/// and `_electrodes.tsv` with `_coordsystem.json` when positions are given.
+pub fn write_bids_recording(
+    root: &str,
+    recording: &Recording,
+    entities: &BidsEntities,
+    options: &BidsOptions,
+) -> Result<()> {
+    entities.check()?;
+    let sampling_rate = recording.sampling_rate;
+
+    let directory = Path::new(root).join(entities.directory());
+    fs::create_dir_all(&directory)?;
+    let base = directory.join(entities.prefix());
+    let path = |suffix: &str| PathBuf::from(format!("{}_{}", base.display(), suffix));
+
+    // Data file, and the channels it holds in the order they are stored
+    let mut channels: Vec<[String; 3]> = Vec::new();
+    match options.format {
+        BidsFormat::BrainVision => {
+            write_brainvision(
+                recording,
+                &path("eeg.vhdr").to_string_lossy(),
+                BinaryFormat::Float32,
+            )?;
+            for channel in &recording.channels {
+                channels.push([
+                    channel.name.clone(),
+                    "EEG".to_string(),
+                    bids_units(&channel.unit),
+                ]);
+            }
+        }
+        BidsFormat::Edf | BidsFormat::Bdf => {
+            let edf_options = match options.format {
+                BidsFormat::Bdf => EdfOptions::bdf(),
+                _ => EdfOptions::edf(),
+            };
+            let extension = match options.format {
+                BidsFormat::Bdf => "eeg.bdf",
+                _ => "eeg.edf",
+            };
+            edf::write(recording, &path(extension).to_string_lossy(), &edf_options)?;
+
+            // Same labels and units as the EDF signals, whose prefix may differ from the channels
+            for (label, units) in edf::signal_units(recording, &edf_options)? {
+                let (kind, units) = match label.as_str() {
+                    "Status" => ("TRIG", "n/a".to_string()),
+                    _ if channels.len() < recording.channels.len() => ("EEG", bids_units(&units)),
+                    _ if units.is_empty() => ("MISC", "n/a".to_string()),
+                    _ => ("MISC", bids_units(&units)),
+                };
+                channels.push([label, kind.to_string(), units]);
+            }
+        }
+    }
+
+    // Channels
+    let mut rows = vec!["name\ttype\tunits\tsampling_frequency\tstatus".to_string()];
+    for [name, kind, units] in &channels {
+        rows.push(format!(
+            "{}\t{}\t{}\t{}\tgood",
+            name, kind, units, sampling_rate
+        ));
+    }
+    write_tsv(&path("channels.tsv"), &rows)?;
+
+    // Events, onsets relative to the first sample
+    let mut rows = vec!["onset\tduration\tsample\tvalue\ttrial_type".to_string()];
+    for event in &recording.events {
+        let trial_type = event
+            .description
+            .as_ref()
+            .map(|description| description.replace(['\t', '\n'], " "))
+            .unwrap_or_else(|| "n/a".to_string());
+        rows.push(format!(
+            "{}\t{}\t{}\t{}\t{}",
+            event.sample_index as Float / sampling_rate,
+            event.duration as Float / sampling_rate,
+            event.sample_index,
+            event.code,
+            trial_type
+        ));
+    }
+    write_tsv(&path("events.tsv"), &rows)?;
+
+    // Electrodes, only those with a known position, named as in the channels table
+    if let Some(positions) = &options.electrode_positions {
+        let mut rows = vec!["name\tx\ty\tz".to_string()];
+        for (channel, [name, ..]) in recording.channels.iter().zip(&channels) {
+            if let Some([x, y, z]) = positions.positions.get(&channel.name) {
+                rows.push(format!("{}\t{}\t{}\t{}", name, x, y, z));
+            }
+        }
+        if rows.len() > 1 {
+            // Positions do not change with the task or run, only sub and ses name them
+            let session_path =
+                |suffix: &str| directory.join(format!("{}_{}", entities.session_prefix(), suffix));
+            write_tsv(&session_path("electrodes.tsv"), &rows)?;
+            write_json(
+                &session_path("coordsystem.json"),
+                &json!({
+                    "EEGCoordinateSystem": positions.coordinate_system,
+                    "EEGCoordinateUnits": positions.units,
+                }),
+            )?;
+        }
+    }
+
+    // Sidecar
+    let count = |kind: &str| channels.iter().filter(|channel| channel[1] == kind).count();
+    let mut sidecar = json!({
+        "TaskName": entities.task,
+        "SamplingFrequency": sampling_rate,
+        "PowerLineFrequency": options
+            .power_line_frequency
+            .map_or(json!("n/a"), |frequency| json!(frequency)),
+        "EEGReference": "n/a",
+        "EEGChannelCount": count("EEG"),
+        "EOGChannelCount": 0,
+        "ECGChannelCount": 0,
+        "EMGChannelCount": 0,
+        "MiscChannelCount": count("MISC"),
+        "TriggerChannelCount": count("TRIG"),
+        "RecordingDuration": recording.duration(),
+        "RecordingType": "continuous",
+    });
+    if is_neuroelectrics(&recording.source) {
+        sidecar["Manufacturer"] = json!("Neuroelectrics");
+    }
+    let settings = &recording.info.eeg_settings;
+    let enabled = |enabled: bool| json!({ "Enabled": enabled });
+    sidecar["SoftwareFilters"] = json!({
+        "LineFilter": enabled(settings.line_filter),
+        "FIRFilter": enabled(settings.fir_filter),
+        "EOGCorrection": enabled(settings.eog_correction),
+        "ReferenceFilter": enabled(settings.reference_filter),
+    });
+    let device = recording.device();
+    let device_fields = [
+        ("ManufacturersModelName", &device.device_class),
+        ("DeviceSerialNumber", &device.device_id),
+        ("SoftwareVersions", &device.software_version),
+    ];
+    for (key, value) in device_fields {
+        if !value.is_empty() {
+            sidecar[key] = json!(value);
+        }
+    }
+    write_json(&path("eeg.json"), &sidecar)
+}
+
+/// Returns `true` if the recording was read from a Neuroelectrics `.easy` or `.nedf` file.
+fn is_neuroelectrics(source: &str) -> bool {
+    let source = source.to_lowercase();
+    [".easy", ".easy.gz", ".nedf"]
+        .iter()
+        .any(|extension| source.ends_with(extension))
+}
+
+/// Writes the microvolt prefix with the micro sign, as BIDS expects SI units.
+fn bids_units(units: &str) -> String {
+    match units {
+        "uV" => "µV".to_string(),
+        _ => units.to_string(),
+    }
+}
+
+/// Writes the lines of a tab-separated file.
+fn write_tsv(path: &Path, rows: &[String]) -> Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    for row in rows {
+        writeln!(file, "{}", row)?;
+    }
+    file.flush()?;
+    Ok(())
+}
+
+/// Writes an indented JSON file.
+fn write_json(path: &Path, value: &Value) -> Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut file, value).map_err(std::io::Error::from)?;
+    writeln!(file)?;
+    file.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{SampleRecording, MONTAGE};
+
+    fn sample_recording() -> Recording {
+        SampleRecording::new(500.0, 10)
+            .eeg(|i, c| if c == 0 { i as i64 } else { c as i64 })
+            .markers(&[(4, 3)])
+            .step_details("Device class: Enobio8\nDevice ID: 0001\n")
+            .settings("Line filter status: ON\n")
+            .montage(&MONTAGE)
+            .triggers(&[(3, "Target")])
+            .recording()
+    }
+
+    #[test]
+    fn test_write_bids() {
+        let recording = sample_recording();
+        let root = std::env::temp_dir().join("easy_rs_bids_test");
+        let _ = fs::remove_dir_all(&root);
+
+        let mut entities = BidsEntities::new("01", "rest");
+        entities.session = Some("1".to_string());
+        let options = BidsOptions {
+            power_line_frequency: Some(50.0),
+            electrode_positions: Some(ElectrodePositions {
+                coordinate_system: "EEGLAB".to_string(),
+                units: "m".to_string(),
+                positions: HashMap::from([("Fp1".to_string(), [0.08, 0.03, -0.01])]),
+            }),
+            ..BidsOptions::default()
+        };
+        write_bids(root.to_str().unwrap(), &[(&recording, entities)], &options).unwrap();
+
+        let eeg_dir = root.join("sub-01/ses-1/eeg");
+        let read = |name: &str| fs::read_to_string(eeg_dir.join(name)).unwrap();
+        for name in ["vhdr", "vmrk", "eeg"] {
+            assert!(eeg_dir
+                .join(format!("sub-01_ses-1_task-rest_eeg.{}", name))
+                .exists());
+        }
+
+        let sidecar: Value =
+            serde_json::from_str(&read("sub-01_ses-1_task-rest_eeg.json")).unwrap();
+        assert_eq!(sidecar["SamplingFrequency"], 500.0);
+        assert_eq!(sidecar["PowerLineFrequency"], 50.0);
+        assert_eq!(sidecar["EEGChannelCount"], 8);
+        assert_eq!(sidecar["SoftwareFilters"]["LineFilter"]["Enabled"], true);
+        assert_eq!(sidecar["SoftwareFilters"]["FIRFilter"]["Enabled"], false);
+        assert_eq!(sidecar["ManufacturersModelName"], "Enobio8");
+
+        let channels = read("sub-01_ses-1_task-rest_channels.tsv");
+        assert_eq!(channels.lines().count(), 9);
+        assert_eq!(channels.lines().nth(1), Some("Fp1\tEEG\tµV\t500\tgood"));
+
+        let events = read("sub-01_ses-1_task-rest_events.tsv");
+        assert_eq!(events.lines().nth(1), Some("0.008\t0.002\t4\t3\tTarget"));
+
+        assert!(sidecar.get("Manufacturer").is_none());
+        let electrodes = read("sub-01_ses-1_electrodes.tsv");
+        assert_eq!(electrodes, "name\tx\ty\tz\nFp1\t0.08\t0.03\t-0.01\n");
+        assert!(eeg_dir.join("sub-01_ses-1_coordsystem.json").exists());
+
+        let participants = fs::read_to_string(root.join("participants.tsv")).unwrap();
+        assert_eq!(participants, "participant_id\nsub-01\n");
+        assert!(root.join("dataset_description.json").exists());
+
+        // EDF signal labels name the channels and the electrodes alike, and the units follow the
+        // prefix of each signal
+        let mut edf_recording = recording.clone();
+        edf_recording.eeg[[0, 1]] = 2e8;
+        edf_recording.source = "recording.easy".to_string();
+        let edf_options = BidsOptions {
+            format: BidsFormat::Edf,
+            ..options.clone()
+        };
+        let mut entities = BidsEntities::new("02", "rest");
+        entities.run = Some(1);
+        write_bids(
+            root.to_str().unwrap(),
+            &[(&edf_recording, entities)],
+            &edf_options,
+        )
+        .unwrap();
+        let eeg_dir = root.join("sub-02/eeg");
+        let read = |name: &str| fs::read_to_string(eeg_dir.join(name)).unwrap();
+        let channels = read("sub-02_task-rest_run-1_channels.tsv");
+        assert_eq!(channels.lines().nth(1), Some("EEG Fp1\tEEG\tµV\t500\tgood"));
+        assert_eq!(channels.lines().nth(2), Some("EEG Fp2\tEEG\tmV\t500\tgood"));
+        let sidecar: Value =
+            serde_json::from_str(&read("sub-02_task-rest_run-1_eeg.json")).unwrap();
+        assert_eq!(sidecar["Manufacturer"], "Neuroelectrics");
+        let electrodes = read("sub-02_electrodes.tsv");
+        assert_eq!(electrodes, "name\tx\ty\tz\nEEG Fp1\t0.08\t0.03\t-0.01\n");
+
+        let invalid = BidsEntities::new("01_a", "rest");
+        assert!(
+            write_bids_recording(root.to_str().unwrap(), &recording, &invalid, &options).is_err()
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_bids_units() {
+        assert_eq!(bids_units("uV"), "µV");
+        assert_eq!(bids_units("nV"), "nV");
+        assert_eq!(bids_units("mV"), "mV");
+        assert_eq!(bids_units("unknown"), "unknown");
+    }
+}
@@ -0,0 +1,274 @@
+use crate::easy_reader::Float;
+use crate::error::{EasyError, Result};
+use crate::recording::Recording;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Sample type of the BrainVision `.eeg` data file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryFormat {
+    /// 32-bit floats in the units of the header, exact up to single precision.
+    Float32,
+    /// 16-bit integers, each channel scaled by its own resolution.
+    Int16,
+}
+
+impl BinaryFormat {
+    /// Value of the `BinaryFormat` header key.
+    fn name(&self) -> &'static str {
+        match self {
+            BinaryFormat::Float32 => "IEEE_FLOAT_32",
+            BinaryFormat::Int16 => "INT_16",
+        }
+    }
+}
+
+/// Writes a recording as a BrainVision triplet.
+///
+/// `filename` is the path of the `.vhdr` header; the `.vmrk` marker file and the `.eeg` data
+/// file are written next to it with the same stem. See [`write_brainvision_to`] for the content.
+pub fn write_brainvision(
+    recording: &Recording,
+    filename: &str,
+    format: BinaryFormat,
+) -> Result<()> {
+    let path = Path::new(filename);
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| EasyError::Unsupported(format!("invalid file name {}", filename)))?;
+
+    let mut header = BufWriter::new(File::create(path.with_extension("vhdr"))?);
+    let mut markers = BufWriter::new(File::create(path.with_extension("vmrk"))?);
+    let mut data = BufWriter::new(File::create(path.with_extension("eeg"))?);
+    write_brainvision_to(
+        recording,
+        stem,
+        format,
+        &mut header,
+        &mut markers,
+        &mut data,
+    )?;
+
+    header.flush()?;
+    markers.flush()?;
+    data.flush()?;
+    Ok(())
+}
+
+/// Writes the header, marker and data files of a BrainVision triplet to any writers.
+///
+/// `stem` names the `.eeg` and `.vmrk` files the header and the marker file refer to. The data
+/// is multiplexed, one sample of every channel after another. With [`BinaryFormat::Int16`],
+/// the resolution of each channel is chosen so that its largest value fits in 16 bits.
+///
+/// Events become `Stimulus` markers described by the trigger table, or by their code when it
+/// has no entry for them.
+pub fn write_brainvision_to<H: Write, M: Write, D: Write>(
+    recording: &Recording,
+    stem: &str,
+    format: BinaryFormat,
+    header: &mut H,
+    markers: &mut M,
+    data: &mut D,
+) -> Result<()> {
+    let eeg = &recording.eeg;
+    let sampling_rate = recording.sampling_rate;
+
+    // Float samples are stored as they are, integer ones divided by the channel resolution
+    let resolutions: Vec<Float> = match format {
+        BinaryFormat::Float32 => vec![1.0; eeg.ncols()],
+        BinaryFormat::Int16 => eeg
+            .columns()
+            .into_iter()
+            .map(|column| {
+                let peak = column.iter().fold(0.0, |peak: Float, x| peak.max(x.abs()));
+                if peak > 0.0 {
+                    peak / i16::MAX as Float
+                } else {
+                    1.0
+                }
+            })
+            .collect(),
+    };
+
+    // Header file
+    writeln!(header, "Brain Vision Data Exchange Header File Version 1.0")?;
+    writeln!(header, "; Data written by easy-rs")?;
+    writeln!(header)?;
+    writeln!(header, "[Common Infos]")?;
+    writeln!(header, "Codepage=UTF-8")?;
+    writeln!(header, "DataFile={}.eeg", stem)?;
+    writeln!(header, "MarkerFile={}.vmrk", stem)?;
+    writeln!(header, "DataFormat=BINARY")?;
+    writeln!(
+        header,
+        "; Data orientation: MULTIPLEXED=ch1,pt1, ch2,pt1 ..."
+    )?;
+    writeln!(header, "DataOrientation=MULTIPLEXED")?;
+    writeln!(header, "NumberOfChannels={}", eeg.ncols())?;
+    writeln!(header, "; Sampling interval in microseconds")?;
+    writeln!(header, "SamplingInterval={}", 1e6 / sampling_rate)?;
+    writeln!(header)?;
+    writeln!(header, "[Binary Infos]")?;
+    writeln!(header, "BinaryFormat={}", format.name())?;
+    writeln!(header)?;
+    writeln!(header, "[Channel Infos]")?;
+    writeln!(
+        header,
+        "; Each entry: Ch<Channel number>=<Name>,<Reference channel name>,"
+    )?;
+    writeln!(header, "; <Resolution in \"Unit\">,<Unit>")?;
+    for (i, (channel, resolution)) in recording.channels.iter().zip(&resolutions).enumerate() {
+        writeln!(
+            header,
+            "Ch{}={},,{},{}",
+            i + 1,
+            escape(&channel.name),
+            resolution,
+            escape(&channel.unit)
+        )?;
+    }
+
+    // Marker file, positions are 1-based
+    writeln!(
+        markers,
+        "Brain Vision Data Exchange Marker File, Version 1.0"
+    )?;
+    writeln!(markers)?;
+    writeln!(markers, "[Common Infos]")?;
+    writeln!(markers, "Codepage=UTF-8")?;
+    writeln!(markers, "DataFile={}.eeg", stem)?;
+    writeln!(markers)?;
+    writeln!(markers, "[Marker Infos]")?;
+    writeln!(
+        markers,
+        "; Each entry: Mk<Marker number>=<Type>,<Description>,<Position in data points>,"
+    )?;
+    writeln!(
+        markers,
+        "; <Size in data points>, <Channel number (0 = marker is related to all channels)>"
+    )?;
+    writeln!(
+        markers,
+        "Mk1=New Segment,,1,1,0,{}",
+        recording.start_time.format("%Y%m%d%H%M%S%6f")
+    )?;
+    for (i, event) in recording.events.iter().enumerate() {
+        writeln!(
+            markers,
+            "Mk{}=Stimulus,{},{},{},0",
+            i + 2,
+            escape(&event.label()),
+            event.sample_index + 1,
+            event.duration
+        )?;
+    }
+
+    // Data file
+    let sample_bytes = match format {
+        BinaryFormat::Float32 => 4,
+        BinaryFormat::Int16 => 2,
+    };
+    let mut bytes = Vec::with_capacity(eeg.ncols() * sample_bytes);
+    for row in eeg.rows() {
+        bytes.clear();
+        for (value, resolution) in row.iter().zip(&resolutions) {
+            match format {
+                BinaryFormat::Float32 => bytes.extend_from_slice(&(*value as f32).to_le_bytes()),
+                BinaryFormat::Int16 => {
+                    let digital = (value / resolution)
+                        .round()
+                        .clamp(i16::MIN as Float, i16::MAX as Float);
+                    bytes.extend_from_slice(&(digital as i16).to_le_bytes())
+                }
+            }
+        }
+        data.write_all(&bytes)?;
+    }
+
+    Ok(())
+}
+
+/// Escapes the commas of a header value, which BrainVision writes as `\1`.
+fn escape(value: &str) -> String {
+    value.replace(',', "\\1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{SampleRecording, MONTAGE};
+
+    fn sample_recording() -> Recording {
+        SampleRecording::new(500.0, 10)
+            .eeg(|i, c| (i as i64 - 5) * 1000 + c as i64)
+            .markers(&[(2, 1), (3, 1), (7, 2)])
+            .montage(&MONTAGE)
+            .triggers(&[(1, "Eyes closed, rest")])
+            .recording()
+    }
+
+    fn write(recording: &Recording, format: BinaryFormat) -> (String, String, Vec<u8>) {
+        let (mut header, mut markers, mut data) = (Vec::new(), Vec::new(), Vec::new());
+        write_brainvision_to(
+            recording,
+            "rec",
+            format,
+            &mut header,
+            &mut markers,
+            &mut data,
+        )
+        .unwrap();
+        (
+            String::from_utf8(header).unwrap(),
+            String::from_utf8(markers).unwrap(),
+            data,
+        )
+    }
+
+    #[test]
+    fn test_write_float32() {
+        let recording = sample_recording();
+        let (header, markers, data) = write(&recording, BinaryFormat::Float32);
+
+        assert!(header.contains("DataFile=rec.eeg\nMarkerFile=rec.vmrk\n"));
+        assert!(header.contains("NumberOfChannels=8\n"));
+        assert!(header.contains("SamplingInterval=2000\n"));
+        assert!(header.contains("BinaryFormat=IEEE_FLOAT_32\n"));
+        assert!(header.contains("Ch1=Fp1,,1,uV\n"));
+        assert!(header.contains("Ch8=O2,,1,uV\n"));
+
+        assert!(markers.contains("Mk1=New Segment,,1,1,0,20210101000000000000\n"));
+        assert!(markers.contains("Mk2=Stimulus,Eyes closed\\1 rest,3,2,0\n"));
+        assert!(markers.contains("Mk3=Stimulus,2,8,1,0\n"));
+        assert!(!markers.contains("Mk4"));
+
+        assert_eq!(data.len(), 10 * 8 * 4);
+        let value = |i: usize| f32::from_le_bytes(data[i * 4..i * 4 + 4].try_into().unwrap());
+        assert_eq!(value(0), -5.0);
+        assert_eq!(value(8 + 3), -3.997);
+    }
+
+    #[test]
+    fn test_write_int16() {
+        let recording = sample_recording();
+        let (header, _, data) = write(&recording, BinaryFormat::Int16);
+
+        assert!(header.contains("BinaryFormat=INT_16\n"));
+        let resolution: Float = header
+            .lines()
+            .find_map(|line| line.strip_prefix("Ch1=Fp1,,"))
+            .and_then(|line| line.split(',').next())
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(resolution, 5.0 / 32767.0);
+
+        assert_eq!(data.len(), 10 * 8 * 2);
+        let value = |i: usize| i16::from_le_bytes([data[i * 2], data[i * 2 + 1]]);
+        assert_eq!(value(0), -32767);
+        assert!((value(8) as Float * resolution - recording.eeg[[1, 0]]).abs() < resolution);
+    }
+}
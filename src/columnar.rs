@@ -0,0 +1,281 @@
+use crate::easy_reader::{Chunk, EasyReader, Float};
+use crate::error::{EasyError, Result};
+use crate::recording::Recording;
+use arrow_array::{ArrayRef, Float64Array, RecordBatch, TimestampMillisecondArray};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{DateTime, Utc};
+use ndarray::{ArrayView2, Axis};
+use parquet::arrow::ArrowWriter;
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::WriterProperties;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::sync::Arc;
+
+/// Names of the accelerometer columns.
+const ACC_COLUMNS: [&str; 3] = ["acc_x", "acc_y", "acc_z"];
+
+/// Returns the Arrow schema of the recording read by `reader`.
+///
+/// There is one `Float64` column per electrode, named after it, then `acc_x`, `acc_y` and
+/// `acc_z` when the file has an accelerometer, `marker`, and `timestamp` as a UTC
+/// `Timestamp(ms)`. The schema metadata holds the `.info` file (see [`metadata`]).
+///
+/// The layout must be known, i.e. the reader was created from a file or the data was read.
+pub fn schema(reader: &EasyReader) -> Result<SchemaRef> {
+    let layout = reader.layout().ok_or_else(|| {
+        EasyError::Unsupported("the layout of the .easy file is unknown".to_string())
+    })?;
+
+    Ok(Arc::new(Schema::new_with_metadata(
+        fields(reader.electrodes(), layout.has_accelerometer()),
+        metadata(reader),
+    )))
+}
+
+/// Returns the Arrow schema of a recording, with the columns described in [`schema`].
+///
+/// The accelerometer columns are at the EEG rate, and `easy.info` holds the metadata of the
+/// recording.
+pub fn recording_schema(recording: &Recording) -> SchemaRef {
+    let mut metadata = HashMap::new();
+    metadata.insert(
+        "easy.eeg_units".to_string(),
+        recording.eeg_unit().to_string(),
+    );
+    metadata.insert(
+        "easy.start_date".to_string(),
+        recording.start_time.to_rfc3339(),
+    );
+    metadata.insert(
+        "easy.sampling_rate".to_string(),
+        recording.sampling_rate.to_string(),
+    );
+    metadata.insert("easy.info".to_string(), recording.info.to_string());
+
+    Arc::new(Schema::new_with_metadata(
+        fields(
+            &recording.channel_names(),
+            recording.accelerometer.is_some(),
+        ),
+        metadata,
+    ))
+}
+
+/// Returns the fields of the EEG, accelerometer, marker and timestamp columns.
+fn fields(electrodes: &[String], accelerometer: bool) -> Vec<Field> {
+    let mut fields: Vec<Field> = electrodes
+        .iter()
+        .map(|electrode| Field::new(electrode, DataType::Float64, false))
+        .collect();
+    if accelerometer {
+        fields.extend(
+            ACC_COLUMNS
+                .iter()
+                .map(|name| Field::new(*name, DataType::Float64, false)),
+        );
+    }
+    fields.push(Field::new("marker", DataType::Float64, false));
+    fields.push(Field::new(
+        "timestamp",
+        DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+        false,
+    ));
+    fields
+}
+
+/// Returns the metadata embedded in Arrow schemas and Parquet files.
+///
+/// `easy.eeg_units` is always present; `easy.start_date` (RFC 3339), `easy.sampling_rate` and
+/// `easy.info`, the content of the `.info` file, are added when known.
+pub fn metadata(reader: &EasyReader) -> HashMap<String, String> {
+    let mut metadata = HashMap::new();
+    metadata.insert("easy.eeg_units".to_string(), reader.eeg_units());
+    if let Some(start_date) = reader.start_date() {
+        metadata.insert("easy.start_date".to_string(), start_date.to_rfc3339());
+    }
+    if let Some(rate) = reader.sampling_rate() {
+        metadata.insert("easy.sampling_rate".to_string(), rate.to_string());
+    }
+    if let Some(info) = reader.info() {
+        metadata.insert("easy.info".to_string(), info.to_string());
+    }
+    metadata
+}
+
+/// Converts a recording into a record batch with the schema of [`recording_schema`].
+pub fn to_record_batch(recording: &Recording) -> Result<RecordBatch> {
+    let acc = recording.accelerometer_at_eeg_rate();
+    let markers = recording.marker_column().insert_axis(Axis(1));
+    record_batch(
+        recording_schema(recording),
+        recording.eeg.view(),
+        acc.as_ref().map(|acc| acc.view()),
+        markers.view(),
+        &recording.timestamps,
+    )
+}
+
+/// Converts a chunk into a record batch with the given schema, as returned by [`schema`].
+pub fn chunk_to_record_batch(chunk: &Chunk, schema: SchemaRef) -> Result<RecordBatch> {
+    record_batch(
+        schema,
+        chunk.eeg.view(),
+        chunk.acc.as_ref().map(|acc| acc.view()),
+        chunk.markers.view(),
+        &chunk.timestamps,
+    )
+}
+
+/// Writes the recording read by `reader` as a Parquet file.
+///
+/// See [`write_parquet_to`].
+pub fn write_parquet(reader: &mut EasyReader, filename: &str, chunk_size: usize) -> Result<()> {
+    let file = File::create(filename)?;
+    write_parquet_to(reader, file, chunk_size)?.sync_all()?;
+    Ok(())
+}
+
+/// Writes the recording read by `reader` in the Parquet format to any writer.
+///
+/// The `.easy` file is read again chunk by chunk, as with [`EasyReader::stream`], and each
+/// chunk of `chunk_size` samples becomes a record batch, so the whole recording is never held
+/// in memory. The [`metadata`] is stored both in the Arrow schema and as key-value metadata of
+/// the Parquet file.
+pub fn write_parquet_to<W: Write + Send>(
+    reader: &mut EasyReader,
+    writer: W,
+    chunk_size: usize,
+) -> Result<W> {
+    // The layout and the start date are known once the reading has started
+    let chunks = reader.chunks(chunk_size)?;
+    let schema = schema(reader)?;
+
+    let key_value_metadata = schema
+        .metadata()
+        .iter()
+        .map(|(key, value)| KeyValue::new(key.clone(), value.clone()))
+        .collect();
+    let properties = WriterProperties::builder()
+        .set_key_value_metadata(Some(key_value_metadata))
+        .build();
+    let mut writer = ArrowWriter::try_new(writer, Arc::clone(&schema), Some(properties))?;
+
+    for chunk in chunks {
+        let batch = chunk_to_record_batch(&chunk?, Arc::clone(&schema))?;
+        writer.write(&batch)?;
+    }
+
+    Ok(writer.into_inner()?)
+}
+
+/// Builds a record batch from the arrays of a recording or a chunk.
+fn record_batch(
+    schema: SchemaRef,
+    eeg: ArrayView2<Float>,
+    acc: Option<ArrayView2<Float>>,
+    markers: ArrayView2<Float>,
+    timestamps: &[DateTime<Utc>],
+) -> Result<RecordBatch> {
+    let column = |values: ndarray::ArrayView1<Float>| -> ArrayRef {
+        Arc::new(Float64Array::from_iter_values(values.iter().copied()))
+    };
+
+    let mut columns: Vec<ArrayRef> = eeg.columns().into_iter().map(column).collect();
+    if let Some(acc) = acc {
+        columns.extend(acc.columns().into_iter().map(column));
+    }
+    columns.push(column(markers.column(0)));
+    columns.push(Arc::new(
+        TimestampMillisecondArray::from_iter_values(
+            timestamps
+                .iter()
+                .map(|timestamp| timestamp.timestamp_millis()),
+        )
+        .with_timezone("UTC"),
+    ));
+
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{SampleRecording, MONTAGE};
+    use arrow_array::Array;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    fn sample() -> SampleRecording {
+        SampleRecording::new(500.0, 25)
+            .eeg(|i, c| if c == 0 { i as i64 * 1000 } else { c as i64 })
+            .accelerometer(|_| [10, 20, 30])
+            .markers(&[(3, 5)])
+            .montage(&MONTAGE)
+            .settings("Accelerometer data: ON\n")
+    }
+
+    #[test]
+    fn test_to_record_batch() {
+        let batch = to_record_batch(&sample().recording()).unwrap();
+
+        assert_eq!(batch.num_rows(), 25);
+        assert_eq!(batch.num_columns(), 8 + 3 + 2);
+        let schema = batch.schema();
+        assert_eq!(schema.field(0).name(), "Fp1");
+        assert_eq!(schema.field(8).name(), "acc_x");
+        assert_eq!(schema.field(11).name(), "marker");
+        assert_eq!(schema.metadata()["easy.sampling_rate"], "500");
+        assert_eq!(schema.metadata()["easy.eeg_units"], "uV");
+        assert!(schema.metadata()["easy.info"].contains("Channel 1: Fp1"));
+
+        let fp1 = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(fp1.value(2), 2.0);
+        let timestamps = batch
+            .column(12)
+            .as_any()
+            .downcast_ref::<TimestampMillisecondArray>()
+            .unwrap();
+        assert_eq!(timestamps.value(1), 1609459200002);
+        assert_eq!(timestamps.timezone(), Some("UTC"));
+    }
+
+    #[test]
+    fn test_write_parquet() {
+        let mut reader = sample().open();
+        let path = std::env::temp_dir().join("easy_rs_columnar_test.parquet");
+        write_parquet(&mut reader, path.to_str().unwrap(), 10).unwrap();
+
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap()).unwrap();
+        let key_value_metadata = builder
+            .metadata()
+            .file_metadata()
+            .key_value_metadata()
+            .unwrap();
+        assert!(key_value_metadata
+            .iter()
+            .any(|kv| kv.key == "easy.start_date"
+                && kv.value.as_deref() == Some("2021-01-01T00:00:00+00:00")));
+
+        let batches: Vec<RecordBatch> = builder
+            .build()
+            .unwrap()
+            .collect::<std::result::Result<_, _>>()
+            .unwrap();
+        let num_rows: usize = batches.iter().map(|batch| batch.num_rows()).sum();
+        assert_eq!(num_rows, 25);
+        let markers = batches[0]
+            .column(11)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(markers.value(3), 5.0);
+        assert!(batches[0].column(11).null_count() == 0);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
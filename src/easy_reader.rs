@@ -1,14 +1,459 @@
-use anyhow::{anyhow, Result};
+use crate::error::{EasyError, Result};
+use crate::info::EEGData;
+use crate::recording::{Event, EventDetector, Recording};
 use chrono::{DateTime, Utc};
 use flate2::read::GzDecoder;
-use ndarray::{s, Array2};
+use ndarray::{s, Array2, ArrayView2, ArrayViewMut2};
+use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
-use std::io::{BufRead, BufReader, Read};
+use std::io::{BufRead, BufReader, Cursor, Read};
+use std::ops::Range;
 use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
 
 const DELIMITER: u8 = b'\t';
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+/// Unit of the EEG values stored in `.easy` files when no `.info` file says otherwise.
+const DEFAULT_EEG_UNITS: &str = "nV";
 pub type Float = f64;
 
+/// Where the `.easy` data is read from.
+enum Source {
+    /// A file on disk, reopened for every pass over the data.
+    Path(String),
+    /// An in-memory blob, shared by every pass over the data.
+    Bytes(Arc<[u8]>),
+    /// An arbitrary reader, consumed by the first pass over the data.
+    Reader(Option<Box<dyn Read>>),
+}
+
+impl fmt::Debug for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Path(path) => f.debug_tuple("Path").field(path).finish(),
+            Source::Bytes(bytes) => write!(f, "Bytes({} bytes)", bytes.len()),
+            Source::Reader(reader) => match reader {
+                Some(_) => write!(f, "Reader"),
+                None => write!(f, "Reader(consumed)"),
+            },
+        }
+    }
+}
+
+/// Column layout of a `.easy` file.
+///
+/// Every row of a `.easy` file holds the EEG channels first and ends with a marker and a
+/// Unix timestamp in milliseconds. Depending on the device settings, three accelerometer
+/// columns (X, Y, Z) may sit between the EEG channels and the marker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// `channels | acc_x | acc_y | acc_z | marker | timestamp` (13, 25 or 37 columns).
+    WithAccelerometer,
+    /// `channels | marker | timestamp` (10, 22 or 34 columns).
+    WithoutAccelerometer,
+}
+
+impl Layout {
+    /// Detects the layout and the number of EEG channels from the number of columns in a row.
+    ///
+    /// Returns `None` if the column count does not match any known NIC device (8, 20 or 32 channels).
+    pub fn detect(num_columns: usize) -> Option<(Layout, usize)> {
+        if [13, 25, 37].contains(&num_columns) {
+            Some((Layout::WithAccelerometer, num_columns - 5))
+        } else if [10, 22, 34].contains(&num_columns) {
+            Some((Layout::WithoutAccelerometer, num_columns - 2))
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if rows with this layout carry accelerometer columns.
+    pub fn has_accelerometer(&self) -> bool {
+        matches!(self, Layout::WithAccelerometer)
+    }
+
+    /// Total number of columns in a row for the given number of EEG channels.
+    pub fn num_columns(&self, num_channels: usize) -> usize {
+        match self {
+            Layout::WithAccelerometer => num_channels + 5,
+            Layout::WithoutAccelerometer => num_channels + 2,
+        }
+    }
+
+    /// Columns holding the EEG channels.
+    pub fn eeg_columns(&self, num_channels: usize) -> Range<usize> {
+        0..num_channels
+    }
+
+    /// Columns holding the X, Y and Z accelerometer axes, if present.
+    pub fn acc_columns(&self, num_channels: usize) -> Option<Range<usize>> {
+        match self {
+            Layout::WithAccelerometer => Some(num_channels..num_channels + 3),
+            Layout::WithoutAccelerometer => None,
+        }
+    }
+
+    /// Column holding the marker.
+    pub fn marker_column(&self, num_channels: usize) -> usize {
+        self.num_columns(num_channels) - 2
+    }
+
+    /// Column holding the Unix timestamp in milliseconds.
+    pub fn timestamp_column(&self, num_channels: usize) -> usize {
+        self.num_columns(num_channels) - 1
+    }
+}
+
+/// A single sample (one row) of a `.easy` file, as yielded by [`EasyReader::samples`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    /// EEG value of each channel, divided by the reader's scale.
+    pub eeg: Vec<Float>,
+    /// X, Y and Z accelerometer values, or `None` if the file has no accelerometer.
+    pub acc: Option<[Float; 3]>,
+    /// Marker value, `0` when no event occurred.
+    pub marker: Float,
+    /// Absolute time of the sample.
+    pub timestamp: DateTime<Utc>,
+}
+
+impl Sample {
+    /// Parses a CSV record according to `layout`, dividing the EEG values by `scale`.
+    fn parse(
+        record: &csv::StringRecord,
+        layout: Layout,
+        num_channels: usize,
+        scale: Float,
+    ) -> Result<Self> {
+        if record.len() != layout.num_columns(num_channels) {
+            return Err(EasyError::ColumnCount {
+                line: line_of(record),
+                found: record.len(),
+            });
+        }
+
+        let eeg = layout
+            .eeg_columns(num_channels)
+            .map(|i| Ok(parse_field::<Float>(record, i)? / scale))
+            .collect::<Result<Vec<Float>>>()?;
+
+        let acc = match layout.acc_columns(num_channels) {
+            Some(columns) => {
+                let mut acc = [0.0; 3];
+                for (value, i) in acc.iter_mut().zip(columns) {
+                    *value = parse_field(record, i)?;
+                }
+                Some(acc)
+            }
+            None => None,
+        };
+
+        let marker = parse_field(record, layout.marker_column(num_channels))?;
+        let timestamp = parse_timestamp(record, layout.timestamp_column(num_channels))?;
+
+        Ok(Sample {
+            eeg,
+            acc,
+            marker,
+            timestamp,
+        })
+    }
+}
+
+/// Reads the first record of the `.easy` data.
+fn first_record(rdr: &mut csv::Reader<Box<dyn Read>>, gzip: bool) -> Result<csv::StringRecord> {
+    match rdr.records().next() {
+        Some(record) => record.map_err(|e| EasyError::from_csv(e, gzip)),
+        None => Err(EasyError::EmptyFile),
+    }
+}
+
+/// Returns the 1-based line number of a record, or `0` if unknown.
+fn line_of(record: &csv::StringRecord) -> u64 {
+    record.position().map_or(0, |position| position.line())
+}
+
+/// Parses the value in `column` of a record, reporting its position on failure.
+fn parse_field<T: FromStr>(record: &csv::StringRecord, column: usize) -> Result<T> {
+    let value = record.get(column).unwrap_or_default();
+    value.trim().parse::<T>().map_err(|_| EasyError::Parse {
+        line: line_of(record),
+        column: column + 1,
+        value: value.to_string(),
+    })
+}
+
+/// Parses a Unix timestamp in milliseconds, as stored in the last column of a `.easy` file.
+fn parse_timestamp(record: &csv::StringRecord, column: usize) -> Result<DateTime<Utc>> {
+    let millis = parse_field::<i64>(record, column)?;
+    DateTime::from_timestamp_millis(millis).ok_or_else(|| EasyError::Parse {
+        line: line_of(record),
+        column: column + 1,
+        value: millis.to_string(),
+    })
+}
+
+/// Error returned when the data is needed before [`EasyReader::parse_data`] was called.
+fn not_loaded() -> EasyError {
+    EasyError::Unsupported("the reader has no data loaded, call parse_data first".to_string())
+}
+
+/// Returns the unit of values expressed in `units` once divided by `scale`.
+///
+/// SI prefixes of volts are shifted by powers of 1000 (`nV` / 1000 = `uV`). Other units are
+/// annotated with the scale.
+pub(crate) fn scaled_units(units: &str, scale: Float) -> String {
+    if scale == 1.0 {
+        return units.to_string();
+    }
+    shift_prefix(units, scale).unwrap_or_else(|| format!("{units} / {scale}"))
+}
+
+/// Shifts the SI prefix of a unit of volts by the power of ten of `scale`, dividing the unit.
+///
+/// Returns `None` if `units` is not a unit of volts or `scale` is not a power of 1000.
+pub(crate) fn shift_prefix(units: &str, scale: Float) -> Option<String> {
+    const PREFIXES: [(&str, i32); 5] = [("p", -12), ("n", -9), ("u", -6), ("m", -3), ("", 0)];
+
+    let exponent = units
+        .strip_suffix('V')
+        .map(|prefix| if prefix == "µ" { "u" } else { prefix })
+        .and_then(|prefix| PREFIXES.iter().find(|(p, _)| *p == prefix))
+        .map(|(_, exponent)| *exponent)?;
+    let shift = scale.log10().round();
+    if ((10.0 as Float).powf(shift) / scale - 1.0).abs() > 1e-9 {
+        return None;
+    }
+
+    let target = exponent + shift as i32;
+    PREFIXES
+        .iter()
+        .find(|(_, e)| *e == target)
+        .map(|(prefix, _)| format!("{prefix}V"))
+}
+
+/// A block of consecutive samples, as passed to the callback of [`EasyReader::stream`].
+///
+/// The arrays follow the same conventions as the corresponding fields of [`EasyReader`]:
+/// one row per sample.
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    /// EEG data, shape `(num_samples, num_channels)`.
+    pub eeg: Array2<Float>,
+    /// Accelerometer data, shape `(num_samples, 3)`, or `None` if the file has no accelerometer.
+    pub acc: Option<Array2<Float>>,
+    /// Markers, shape `(num_samples, 1)`.
+    pub markers: Array2<Float>,
+    /// Time of each sample in seconds relative to the start of the recording, shape `(num_samples, 1)`.
+    pub time: Array2<Float>,
+    /// Absolute time of each sample.
+    pub timestamps: Vec<DateTime<Utc>>,
+    /// Events starting in the chunk, indexed from the first sample of the recording.
+    ///
+    /// An event whose marker is held into the next chunk is reported once, with the number of
+    /// samples it lasts in this chunk.
+    pub events: Vec<Event>,
+}
+
+impl Chunk {
+    /// Number of samples in the chunk.
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    /// Returns `true` if the chunk holds no samples.
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+}
+
+/// Accumulates parsed samples column by column until they are turned into a [`Chunk`].
+#[derive(Default)]
+struct ChunkBuilder {
+    eeg: Vec<Float>,
+    acc: Vec<Float>,
+    markers: Vec<Float>,
+    time: Vec<Float>,
+    timestamps: Vec<DateTime<Utc>>,
+    events: Vec<Event>,
+    /// Kept across chunks, events may span several of them.
+    detector: EventDetector,
+    /// Trigger table describing the events.
+    triggers: HashMap<u32, String>,
+}
+
+impl ChunkBuilder {
+    fn new(triggers: HashMap<u32, String>) -> Self {
+        ChunkBuilder {
+            triggers,
+            ..ChunkBuilder::default()
+        }
+    }
+
+    /// Appends a sample, computing its time relative to `start`.
+    fn push(&mut self, sample: Sample, start: DateTime<Utc>) {
+        self.eeg.extend(sample.eeg);
+        if let Some(acc) = sample.acc {
+            self.acc.extend(acc);
+        }
+        self.detector.push(
+            sample.marker,
+            sample.timestamp,
+            start,
+            &self.triggers,
+            &mut self.events,
+        );
+        self.markers.push(sample.marker);
+        self.time
+            .push((sample.timestamp - start).num_milliseconds() as Float / 1000.0);
+        self.timestamps.push(sample.timestamp);
+    }
+
+    fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    /// Turns the accumulated samples into a chunk, leaving the builder empty.
+    fn take(&mut self, layout: Layout, num_channels: usize) -> Result<Chunk> {
+        let num_samples = self.len();
+        let acc = std::mem::take(&mut self.acc);
+
+        Ok(Chunk {
+            eeg: Array2::from_shape_vec(
+                (num_samples, num_channels),
+                std::mem::take(&mut self.eeg),
+            )?,
+            acc: if layout.has_accelerometer() {
+                Some(Array2::from_shape_vec((num_samples, 3), acc)?)
+            } else {
+                None
+            },
+            markers: Array2::from_shape_vec((num_samples, 1), std::mem::take(&mut self.markers))?,
+            time: Array2::from_shape_vec((num_samples, 1), std::mem::take(&mut self.time))?,
+            timestamps: std::mem::take(&mut self.timestamps),
+            events: std::mem::take(&mut self.events),
+        })
+    }
+}
+
+/// Iterator over the samples of a `.easy` file, created by [`EasyReader::samples`].
+///
+/// Rows are parsed lazily as the iterator advances, so dropping it early stops reading the file.
+pub struct Samples {
+    records: csv::StringRecordsIntoIter<Box<dyn Read>>,
+    first_record: Option<csv::StringRecord>,
+    layout: Layout,
+    num_channels: usize,
+    scale: Float,
+    gzip: bool,
+    failed: bool,
+}
+
+impl Samples {
+    /// Column layout of the file being read.
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Number of EEG channels in each sample.
+    pub fn num_channels(&self) -> usize {
+        self.num_channels
+    }
+}
+
+impl Iterator for Samples {
+    type Item = Result<Sample>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        // The first record is a sample like any other
+        let record = match self.first_record.take() {
+            Some(record) => Ok(record),
+            None => self
+                .records
+                .next()?
+                .map_err(|e| EasyError::from_csv(e, self.gzip)),
+        };
+
+        let sample = record
+            .and_then(|record| Sample::parse(&record, self.layout, self.num_channels, self.scale));
+        // Stop after the first error, the rest of the file cannot be trusted
+        self.failed = sample.is_err();
+        Some(sample)
+    }
+}
+
+/// Iterator over blocks of consecutive samples, created by [`EasyReader::chunks`].
+///
+/// Each [`Chunk`] owns its arrays, no data is copied between the reader and the caller. When a
+/// row fails to parse, the samples before it are yielded as a last, shorter chunk, followed by
+/// the error.
+pub struct Chunks {
+    samples: Samples,
+    start_date: DateTime<Utc>,
+    chunk_size: usize,
+    builder: ChunkBuilder,
+    /// Error met while filling the chunk yielded last, returned on the next call.
+    error: Option<EasyError>,
+}
+
+impl Iterator for Chunks {
+    type Item = Result<Chunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(e) = self.error.take() {
+            return Some(Err(e));
+        }
+
+        for sample in self.samples.by_ref() {
+            match sample {
+                Ok(sample) => self.builder.push(sample, self.start_date),
+                // The samples read so far come first, in file order
+                Err(e) if self.builder.len() > 0 => {
+                    self.error = Some(e);
+                    break;
+                }
+                Err(e) => return Some(Err(e)),
+            }
+            if self.builder.len() >= self.chunk_size {
+                break;
+            }
+        }
+
+        if self.builder.len() == 0 {
+            return None;
+        }
+        Some(
+            self.builder
+                .take(self.samples.layout, self.samples.num_channels),
+        )
+    }
+}
+
+/// Owned data of a loaded recording, as returned by [`EasyReader::into_parts`].
+#[derive(Debug, Clone)]
+pub struct EasyParts {
+    /// Names of the EEG channels, in column order.
+    pub electrodes: Vec<String>,
+    /// Time of the first sample.
+    pub start_date: Option<DateTime<Utc>>,
+    /// EEG data, shape `(num_samples, num_channels)`.
+    pub eeg: Array2<Float>,
+    /// Accelerometer data, shape `(num_samples, 3)`, if the file has any.
+    pub acc: Option<Array2<Float>>,
+    /// Markers, shape `(num_samples, 1)`.
+    pub markers: Array2<Float>,
+    /// Time of each sample in seconds relative to the start of the recording, shape `(num_samples, 1)`.
+    pub time: Array2<Float>,
+    /// Absolute time of each sample.
+    pub timestamps: Vec<DateTime<Utc>>,
+}
+
 /// Struct representing a reader for EEG data stored in `.easy` files.
 ///
 /// This struct is responsible for parsing and storing the data from a `.easy` file,
@@ -24,10 +469,14 @@ pub struct EasyReader {
 
     verbose: bool,
 
+    /// Source of the `.easy` data.
+    source: Source,
+
     /// Path to the `.easy` file being read.
     ///
     /// This is the full path to the `.easy` file that contains the EEG and accelerometer data.
-    /// The file is parsed to extract the signals and metadata.
+    /// The file is parsed to extract the signals and metadata. It is empty when the data does not
+    /// come from a file.
     filepath: String,
 
     /// Base name of the file without the extension.
@@ -51,11 +500,20 @@ pub struct EasyReader {
     /// If available, this file provides information about the electrode names and other metadata.
     infofilepath: String,
 
+    /// Metadata parsed from the `.info` file, if available.
+    ///
+    /// Provides the montage used to name the channels, the sampling rate, the EEG units
+    /// and the accelerometer settings.
+    info: Option<EEGData>,
+
     /// Flag indicating whether accelerometer data is present.
     ///
-    /// This flag is set to `true` if accelerometer data is found in the `.easy` file or the `.info` file.
+    /// This flag is set from the column layout of the `.easy` file.
     acc_data: bool,
 
+    /// Column layout of the `.easy` file, detected from its first row.
+    layout: Option<Layout>,
+
     /// List of electrode names.
     ///
     /// If the `.info` file is available, this field will contain the names of the EEG channels (electrodes).
@@ -72,13 +530,19 @@ pub struct EasyReader {
     ///
     /// This date is extracted from the first timestamp in the `.easy` file. It represents the
     /// time when the EEG recording began.
-    eegstartdate: Option<String>,
+    eegstartdate: Option<DateTime<Utc>>,
 
     /// Array representing the time vector of the dataset in seconds.
     ///
     /// This array contains the time of each sample relative to the start of the recording.
+    /// It has shape `(num_samples, 1)`.
     np_time: Option<Array2<Float>>,
 
+    /// Absolute time of each sample.
+    ///
+    /// Parsed from the Unix timestamp in milliseconds stored in the last column of the `.easy` file.
+    timestamps: Option<Vec<DateTime<Utc>>>,
+
     /// 2D array of EEG data.
     ///
     /// This is a 2D array where each row represents an EEG sample, and each column represents
@@ -103,6 +567,11 @@ pub struct EasyReader {
     /// in the EEG signal. Markers are typically used to mark specific moments in time during the recording.
     np_markers: Option<Array2<Float>>,
 
+    /// Events found in the markers.
+    ///
+    /// This is `None` until the data is loaded.
+    events: Option<Vec<Event>>,
+
     /// Log of the events related to the processing of the `.easy` file.
     ///
     /// This is a collection of strings that logs important events, like the creation of the `EasyReader` instance
@@ -117,121 +586,248 @@ impl EasyReader {
             println!("Initializing in file path: {}", filepath);
         }
 
-        let extension;
-        let (filenameroot, basename) = if filepath.ends_with(".easy.gz") {
-            extension = "easy.gz".to_string();
-            let filenameroot = filepath.trim_end_matches(".gz");
-            let basename = Path::new(filepath)
-                .file_name()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .trim_end_matches(".gz")
-                .to_string();
-            (filenameroot.to_string(), basename)
-        } else if filepath.ends_with(".easy") {
-            extension = "easy".to_string();
-            let filenameroot = filepath.trim_end_matches(".easy");
-            let basename = Path::new(filepath)
-                .file_name()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .trim_end_matches(".easy")
-                .to_string();
-            (filenameroot.to_string(), basename)
+        let (extension, filenameroot) = if let Some(root) = filepath.strip_suffix(".easy.gz") {
+            ("easy.gz".to_string(), root.to_string())
+        } else if let Some(root) = filepath.strip_suffix(".easy") {
+            ("easy".to_string(), root.to_string())
         } else {
-            return Err(anyhow!("ERROR: Proposed file has wrong extension."));
+            return Err(EasyError::BadExtension(filepath.to_string()));
         };
+        let basename = Path::new(&filenameroot)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
 
         let infofilepath = format!("{}.info", filenameroot);
 
-        let mut reader = EasyReader {
+        let mut reader = Self::with_source(Source::Path(filepath.to_string()), scale, verbose);
+        reader.filepath = filepath.to_string();
+        reader.basename = basename;
+        reader.extension = extension;
+        reader.filenameroot = filenameroot;
+        reader.infofilepath = infofilepath;
+
+        // Try to read the info file, a missing info file is not an error
+        if let Ok(file) = File::open(&reader.infofilepath) {
+            reader.get_info(BufReader::new(file))?;
+        }
+
+        // The layout always comes from the data itself
+        reader.read_easy_file_for_channels()?;
+
+        Ok(reader)
+    }
+
+    /// Initializes a new `EasyReader` instance with the [`default_scale`](Self::default_scale)
+    /// of its `.info` file, which loads the EEG in microvolts.
+    pub fn open(filepath: &str, verbose: bool) -> Result<Self> {
+        let mut reader = Self::new(filepath, 1.0, verbose)?;
+        reader.scale = reader.default_scale();
+        reader
+            .log
+            .push(format!("scale {} from the .info units", reader.scale));
+        Ok(reader)
+    }
+
+    /// Initializes a new `EasyReader` instance from an in-memory `.easy` or `.easy.gz` blob.
+    ///
+    /// Compression is detected from the gzip magic bytes. `info` is the content of the matching
+    /// `.info` file, if available. The data can be read any number of times.
+    pub fn from_bytes(
+        data: impl Into<Vec<u8>>,
+        info: Option<&str>,
+        scale: Float,
+        verbose: bool,
+    ) -> Result<Self> {
+        let data: Vec<u8> = data.into();
+        let mut reader = Self::with_source(Source::Bytes(data.into()), scale, verbose);
+
+        if let Some(info) = info {
+            reader.get_info(info.as_bytes())?;
+        }
+        reader.read_easy_file_for_channels()?;
+
+        Ok(reader)
+    }
+
+    /// Initializes a new `EasyReader` instance from any reader over `.easy` or `.easy.gz` data.
+    ///
+    /// Compression is detected from the gzip magic bytes. `info` is the content of the matching
+    /// `.info` file, if available. The reader can only be consumed once: the first call to
+    /// [`parse_data`](Self::parse_data), [`stream`](Self::stream), [`samples`](Self::samples) or
+    /// [`chunks`](Self::chunks) reads it, later calls fail with an I/O error. The layout and the
+    /// number of channels are only known after that first pass.
+    pub fn from_reader<R: Read + 'static>(
+        reader: R,
+        info: Option<&str>,
+        scale: Float,
+        verbose: bool,
+    ) -> Result<Self> {
+        let mut easy_reader =
+            Self::with_source(Source::Reader(Some(Box::new(reader))), scale, verbose);
+
+        if let Some(info) = info {
+            easy_reader.get_info(info.as_bytes())?;
+        }
+
+        Ok(easy_reader)
+    }
+
+    /// Creates a reader with no metadata nor data loaded yet.
+    fn with_source(source: Source, scale: Float, verbose: bool) -> Self {
+        EasyReader {
             scale,
             verbose,
-            filepath: filepath.to_string(),
-            basename,
-            extension,
-            filenameroot,
-            infofilepath,
+            source,
+            filepath: String::new(),
+            basename: String::new(),
+            extension: String::new(),
+            filenameroot: String::new(),
+            infofilepath: String::new(),
+            info: None,
             acc_data: false,
+            layout: None,
             electrodes: Vec::new(),
             num_channels: None,
             eegstartdate: None,
             np_time: None,
+            timestamps: None,
             np_eeg: None,
             np_stim: None,
             np_acc: None,
             np_markers: None,
+            events: None,
             log: vec![format!("capsule created: {}", Utc::now())],
-        };
-
-        // Try to read the info file
-        reader.get_info()?;
-
-        Ok(reader)
+        }
     }
 
-    /// Reads and processes the `.info` file for metadata about channels and accelerometer data.
-    fn get_info(&mut self) -> Result<()> {
-        let file = File::open(&self.infofilepath);
-
-        match file {
-            Ok(file) => {
-                let reader = BufReader::new(file);
-                let mut electrodes = Vec::new();
-                let mut acc_data = false;
-
-                for line in reader.lines() {
-                    let line = line.unwrap();
-                    if line.contains("Channel ") {
-                        let electrode = line.split_whitespace().last().unwrap().to_string();
-                        electrodes.push(electrode);
-                    }
-                    if line.contains("Accelerometer data: ") {
-                        acc_data = true;
-                    }
-                }
+    /// Reads and processes the `.info` file for metadata about the recording.
+    ///
+    /// The channels are named after the montage, ordered by channel number.
+    fn get_info<R: BufRead>(&mut self, reader: R) -> Result<()> {
+        let info = EEGData::parse_reader(reader)?;
 
-                self.electrodes = electrodes;
-                self.acc_data = acc_data;
-                self.num_channels = Some(self.electrodes.len());
+        self.electrodes = info.electrodes();
+        if !self.electrodes.is_empty() {
+            self.num_channels = Some(self.electrodes.len());
+        }
 
-                Ok(())
-            }
-            Err(_) => {
-                // If no info file is found, read the .easy file to determine the number of channels
-                self.read_easy_file_for_channels()
-            }
+        if self.verbose {
+            println!(
+                "Info loaded: {} electrodes, {} Hz, units {:?}",
+                self.electrodes.len(),
+                info.eeg_settings.sampling_rate,
+                info.eeg_settings.eeg_units
+            );
         }
+
+        self.info = Some(info);
+        Ok(())
     }
 
-    /// Reads the `.easy` file to determine the number of channels based on the file structure.
+    /// Reads the `.easy` file to determine the column layout and the number of channels.
     fn read_easy_file_for_channels(&mut self) -> Result<()> {
-        let reader = self.get_file_reader(&self.filepath)?;
+        let (mut rdr, gzip) = self.csv_reader()?;
+        let first_record = first_record(&mut rdr, gzip)?;
+
+        let (layout, _) = self.detect_layout(&first_record)?;
+
+        if self.verbose {
+            println!("Layout detected: {:?}", layout);
+        }
 
-        let mut rdr = csv::ReaderBuilder::new()
+        Ok(())
+    }
+
+    /// Opens a tab-delimited reader over the `.easy` data.
+    ///
+    /// Also returns whether the data is gzip-compressed.
+    fn csv_reader(&mut self) -> Result<(csv::Reader<Box<dyn Read>>, bool)> {
+        let (reader, gzip) = self.get_file_reader()?;
+        let rdr = csv::ReaderBuilder::new()
             .delimiter(DELIMITER)
             .has_headers(false)
             .from_reader(reader);
+        Ok((rdr, gzip))
+    }
 
-        // Read the first 5 lines to determine number of columns
-        let mut header = rdr.records().take(5);
-        let first_record = header.next().unwrap().unwrap();
-
-        let num_columns = first_record.len();
-
-        let num_channels = if [13, 25, 37].contains(&num_columns) {
-            num_columns - 5
-        } else if [10, 22, 34].contains(&num_columns) {
-            num_columns - 2
+    /// Returns the layout and number of channels declared by the montage and the accelerometer
+    /// settings of the `.info` file.
+    fn declared_layout(&self) -> Option<(Layout, usize)> {
+        let info = self.info.as_ref()?;
+        let num_channels = info.eeg_settings.montage.len();
+        let layout = if info.eeg_settings.accelerometer.is_some() {
+            Layout::WithAccelerometer
         } else {
-            return Err(anyhow!("Number of columns mismatch with expected values."));
+            Layout::WithoutAccelerometer
         };
+        (num_channels > 0).then_some((layout, num_channels))
+    }
+
+    /// Detects the column layout from a record and records it on the reader.
+    ///
+    /// The layout declared by the `.info` file is used when the record has its columns, which
+    /// allows channel counts of no known device. Otherwise the layout is detected from the
+    /// number of columns. Channels get default names if the `.info` file did not provide any, or
+    /// listed a different number of them.
+    fn detect_layout(&mut self, record: &csv::StringRecord) -> Result<(Layout, usize)> {
+        let (layout, num_channels) = match self.declared_layout() {
+            Some((layout, num_channels)) if layout.num_columns(num_channels) == record.len() => {
+                (layout, num_channels)
+            }
+            _ => Layout::detect(record.len()).ok_or_else(|| EasyError::ColumnCount {
+                line: line_of(record),
+                found: record.len(),
+            })?,
+        };
+
+        // A montage that does not fit the data is left to `validate` to report
+        if self.electrodes.len() != num_channels {
+            if !self.electrodes.is_empty() {
+                self.log.push(format!(
+                    "{} channels listed in the .info file, {} found in the data, using default names",
+                    self.electrodes.len(),
+                    num_channels
+                ));
+            }
+            self.electrodes = (1..=num_channels).map(|x| format!("Ch{}", x)).collect();
+        }
+
+        // The data is authoritative, but a disagreement with the `.info` file is worth noting once
+        if self.layout.is_none() {
+            if let Some(info) = &self.info {
+                let declared = info.eeg_settings.accelerometer.is_some();
+                if declared != layout.has_accelerometer() {
+                    self.log.push(format!(
+                        "accelerometer declared {} in the .info file but {} in the data",
+                        if declared { "ON" } else { "OFF" },
+                        if layout.has_accelerometer() {
+                            "present"
+                        } else {
+                            "absent"
+                        }
+                    ));
+                }
+            }
+        }
 
         self.num_channels = Some(num_channels);
-        self.electrodes = (1..=num_channels).map(|x| format!("Ch{}", x)).collect();
-        Ok(())
+        self.layout = Some(layout);
+        self.acc_data = layout.has_accelerometer();
+
+        Ok((layout, num_channels))
+    }
+
+    /// Records the start date of the recording from the timestamp column of the first record.
+    fn read_start_date(
+        &mut self,
+        record: &csv::StringRecord,
+        layout: Layout,
+        num_channels: usize,
+    ) -> Result<DateTime<Utc>> {
+        let start_date = parse_timestamp(record, layout.timestamp_column(num_channels))?;
+        self.eegstartdate = Some(start_date);
+        Ok(start_date)
     }
 
     /// Reads and processes raw EEG and accelerometer data from the `.easy` file.
@@ -245,110 +841,105 @@ impl EasyReader {
     /// # Returns
     ///
     /// - `Ok(())` if the data was successfully read and processed.
-    /// - `Err(String)` if there was an error reading or processing the file data. The error
-    ///   string provides details about the failure (e.g., column mismatches or data format issues).
+    /// - `Err(EasyError)` if there was an error reading or processing the file data. The error
+    ///   provides details about the failure (e.g., column mismatches with the offending line, or
+    ///   values that cannot be parsed with their line and column).
     ///
     /// # Details
     ///
     /// - The function expects the `.easy` file to have the following general format:
     ///   EEG data followed by accelerometer data (if available), markers, and timestamps.
     /// - The EEG data is divided by channels, and the accelerometer data (if present) consists
-    ///   of three columns representing X, Y, and Z axes.
-
+    ///   of three columns representing X, Y, and Z axes. For files recorded without
+    ///   accelerometer, `np_acc` is left as `None`.
     pub fn parse_data(&mut self) -> Result<()> {
-        let reader = self.get_file_reader(&self.filepath)?;
-        let mut rdr = csv::ReaderBuilder::new()
-            .delimiter(DELIMITER)
-            .has_headers(false)
-            .from_reader(reader);
-
-        let mut records = rdr.records();
-        let first_record = records.next().unwrap().unwrap();
-
-        if self.verbose {
-            println!("first_record - {first_record:?}");
-        }
-
-        let num_columns = first_record.len();
-
-        let num_channels = if [13, 25, 37].contains(&num_columns) {
-            num_columns - 5
-        } else if [10, 22, 34].contains(&num_columns) {
-            num_columns - 2
-        } else {
-            return Err(anyhow!("Number of columns mismatch with expected values."));
-        };
-
-        // Handle timestamp
-        let timestamp = first_record[first_record.len() - 1].parse::<u64>().unwrap();
-        if let Some(start_date) = DateTime::from_timestamp((timestamp / 1000) as i64, 0) {
-            self.eegstartdate = Some(start_date.format("%Y-%m-%d %H:%M:%S").to_string());
-        }
+        let samples = self.samples()?;
+        let (layout, num_channels) = (samples.layout(), samples.num_channels());
+        let start_date = self.eegstartdate.ok_or(EasyError::EmptyFile)?;
 
         if self.verbose {
             println!("Number of channels detected: {}", num_channels);
             println!(
                 "First sample recorded: {}",
-                self.eegstartdate.clone().unwrap()
+                start_date.format("%Y-%m-%d %H:%M:%S%.3f")
             );
         }
 
-        // Read the rest of the file into numpy-like data
-        let mut eeg_data = Vec::new();
-        let mut acc_data = Vec::new();
-        let mut markers = Vec::new();
+        // Read the whole file into numpy-like data
+        let mut builder = ChunkBuilder::new(self.triggers());
+        for sample in samples {
+            builder.push(sample?, start_date);
+        }
 
-        for record in records {
-            let record = record.unwrap();
-            let eeg_values: Vec<Float> = record
-                .iter()
-                .take(num_channels)
-                .map(|x| x.parse::<Float>().unwrap())
-                .map(|f| f / self.scale)
-                .collect();
-            let acc_values: Vec<Float> = record
-                .iter()
-                .skip(num_channels)
-                .take(3)
-                .map(|x| x.parse::<Float>().unwrap())
-                .collect();
-            let marker_value: Float = record[num_channels + 3].parse().unwrap();
-
-            eeg_data.push(eeg_values);
-            acc_data.push(acc_values);
-            markers.push(marker_value);
-        }
-
-        self.np_eeg = Some(
-            Array2::from_shape_vec(
-                (eeg_data.len(), num_channels),
-                eeg_data.into_iter().flatten().collect(),
-            )
-            .unwrap(),
-        );
-        self.np_acc = Some(
-            Array2::from_shape_vec(
-                (acc_data.len(), 3),
-                acc_data.into_iter().flatten().collect(),
-            )
-            .unwrap(),
-        );
-        self.np_markers = Some(Array2::from_shape_vec((markers.len(), 1), markers).unwrap());
+        let data = builder.take(layout, num_channels)?;
+        self.np_eeg = Some(data.eeg);
+        self.np_acc = data.acc;
+        self.np_markers = Some(data.markers);
+        self.np_time = Some(data.time);
+        self.timestamps = Some(data.timestamps);
+        self.events = Some(data.events);
 
         Ok(())
     }
 
+    /// Returns an iterator over the samples of the `.easy` file.
+    ///
+    /// The file is read lazily, one row per call to `next`, and nothing is stored on the reader
+    /// apart from the detected layout and the start date. The iterator stops after the first error.
+    ///
+    /// ```no_run
+    /// # use easy_rs::easy_reader::EasyReader;
+    /// let mut reader = EasyReader::new("data/Example01.easy", 1.0, false)?;
+    /// let first_marker = reader
+    ///     .samples()?
+    ///     .filter_map(|sample| sample.ok())
+    ///     .find(|sample| sample.marker != 0.0);
+    /// # Ok::<(), easy_rs::error::EasyError>(())
+    /// ```
+    pub fn samples(&mut self) -> Result<Samples> {
+        let (mut rdr, gzip) = self.csv_reader()?;
+        let first_record = first_record(&mut rdr, gzip)?;
+
+        let (layout, num_channels) = self.detect_layout(&first_record)?;
+        self.read_start_date(&first_record, layout, num_channels)?;
+
+        Ok(Samples {
+            records: rdr.into_records(),
+            first_record: Some(first_record),
+            layout,
+            num_channels,
+            scale: self.scale,
+            gzip,
+            failed: false,
+        })
+    }
+
+    /// Returns an iterator over blocks of at most `chunk_size` consecutive samples.
+    ///
+    /// This is the iterator counterpart of [`stream`](Self::stream).
+    pub fn chunks(&mut self, chunk_size: usize) -> Result<Chunks> {
+        let samples = self.samples()?;
+        let start_date = self.eegstartdate.ok_or(EasyError::EmptyFile)?;
+
+        Ok(Chunks {
+            samples,
+            start_date,
+            chunk_size: chunk_size.max(1),
+            builder: ChunkBuilder::new(self.triggers()),
+            error: None,
+        })
+    }
+
     /// Reads and processes raw EEG and accelerometer data from the `.easy` file in a streaming manner.
     ///
     /// This function reads the `.easy` file in chunks and processes each chunk as it is read. This approach
     /// helps to minimize memory usage when dealing with large files by avoiding the need to load the entire
-    /// file into memory at once.
+    /// file into memory at once. See [`chunks`](Self::chunks) and [`samples`](Self::samples) for iterator
+    /// based alternatives.
     ///
     /// The function uses a callback (`process_chunk`) to handle each chunk of data. The callback is invoked
-    /// after processing each chunk, and it receives the following data:
-    /// - `eeg_chunk`: A `Vec<Vec<f32>>` representing a chunk of EEG data (one row per sample, one column per channel).
-    /// - `acc_chunk`: A `Vec<Vec<f32>>` representing a chunk of accelerometer data (three values per sample: X, Y, Z).
-    /// - `markers_chunk`: A `Vec<f32>` representing the marker data for each sample in the chunk.
+    /// after processing each chunk, and it receives a [`Chunk`] holding the EEG data, the accelerometer data
+    /// (if the file has any), the markers, and the relative and absolute time of each sample in the chunk.
     ///
     /// The chunk size can be customized by passing a `chunk_size` value (in number of rows). If no chunk size
     /// is provided, the default chunk size will be `1000` rows.
@@ -356,112 +947,296 @@ impl EasyReader {
     /// # Parameters:
     /// - `chunk_size`: An optional parameter specifying the number of rows to process per chunk. If `None`
     ///   is provided, the default chunk size will be `1000`.
-    /// - `process_chunk`: A callback function that takes three arguments: `eeg_chunk`, `acc_chunk`, and
-    ///   `markers_chunk`. This function will be called once a chunk is read and parsed.
+    /// - `process_chunk`: A callback function that takes the [`Chunk`]. This function will be called once
+    ///   a chunk is read and parsed.
     ///
     /// # Returns:
     /// - `Ok(())` if the data was successfully read and processed.
-    /// - `Err(String)` if there was an error
+    /// - `Err(EasyError)` if there was an error reading or parsing the file. Chunks read before the
+    ///   error have already been passed to `process_chunk`.
     pub fn stream<F>(&mut self, chunk_size: Option<usize>, mut process_chunk: F) -> Result<()>
     where
-        F: FnMut(Vec<Vec<Float>>, Vec<Vec<Float>>, Vec<Float>), // Callback to process each chunk of data
+        F: FnMut(Chunk), // Callback to process each chunk of data
     {
-        let chunk_size = match chunk_size {
-            Some(chunk_size) => chunk_size,
-            None => 1000,
-        };
-        let reader = self.get_file_reader(&self.filepath)?;
-        let mut rdr = csv::ReaderBuilder::new()
-            .delimiter(DELIMITER)
-            .has_headers(false)
-            .from_reader(reader);
-
-        let mut records = rdr.records();
-        let first_record = records.next().unwrap().unwrap();
-
-        let num_columns = first_record.len();
-        let num_channels = if [13, 25, 37].contains(&num_columns) {
-            num_columns - 5
-        } else if [10, 22, 34].contains(&num_columns) {
-            num_columns - 2
-        } else {
-            return Err(anyhow!("Number of columns mismatch with expected values."));
-        };
-
-        // Handle timestamp
-        let timestamp = first_record[first_record.len() - 1].parse::<u64>().unwrap();
-        if let Some(start_date) = DateTime::from_timestamp((timestamp / 1000) as i64, 0) {
-            self.eegstartdate = Some(start_date.format("%Y-%m-%d %H:%M:%S").to_string());
-        }
+        let chunks = self.chunks(chunk_size.unwrap_or(1000))?;
 
         if self.verbose {
-            println!(
-                "First sample recorded: {}",
-                self.eegstartdate.clone().unwrap()
-            );
-        }
-
-        // Process the records in chunks
-        let mut eeg_chunk = Vec::new();
-        let mut acc_chunk = Vec::new();
-        let mut markers_chunk = Vec::new();
-
-        for record in records {
-            let record = record.unwrap();
-
-            // Process EEG data (channels)
-            let eeg_values: Vec<Float> = record
-                .iter()
-                .take(num_channels)
-                .map(|x| x.parse::<Float>().unwrap())
-                .map(|f| f / self.scale)
-                .collect();
-            eeg_chunk.push(eeg_values);
-
-            // Process accelerometer data (3 axes)
-            let acc_values: Vec<Float> = record
-                .iter()
-                .skip(num_channels)
-                .take(3)
-                .map(|x| x.parse::<Float>().unwrap())
-                .collect();
-            acc_chunk.push(acc_values);
-
-            // Process marker data
-            let marker_value: Float = record[num_channels + 3].parse().unwrap();
-            markers_chunk.push(marker_value);
-
-            // Once a chunk is ready, call the callback to process the chunk
-            if eeg_chunk.len() >= chunk_size {
-                // Process every 1000 rows as a chunk
-                process_chunk(eeg_chunk.clone(), acc_chunk.clone(), markers_chunk.clone());
-                // Clear the chunk data after processing
-                eeg_chunk.clear();
-                acc_chunk.clear();
-                markers_chunk.clear();
+            if let Some(start_date) = self.eegstartdate {
+                println!(
+                    "First sample recorded: {}",
+                    start_date.format("%Y-%m-%d %H:%M:%S%.3f")
+                );
             }
         }
 
-        // Process any remaining data in the chunk
-        if !eeg_chunk.is_empty() {
-            process_chunk(eeg_chunk, acc_chunk, markers_chunk);
+        // Once a chunk is ready, call the callback to process the chunk
+        for chunk in chunks {
+            process_chunk(chunk?);
         }
 
         Ok(())
     }
 
-    /// Helper function to get a reader for the file, whether it's gzipped or not.
-    fn get_file_reader(&self, filepath: &str) -> Result<Box<dyn Read>> {
-        if filepath.ends_with(".gz") {
-            let file = File::open(filepath).map_err(|e| anyhow!(e.to_string()))?;
-            let decoder = GzDecoder::new(file);
-            Ok(Box::new(decoder))
+    /// Helper function to get a reader for the data, whether it's gzipped or not.
+    ///
+    /// Compression is detected from the gzip magic bytes, not from the file name.
+    fn get_file_reader(&mut self) -> Result<(Box<dyn Read>, bool)> {
+        let reader: Box<dyn Read> = match &mut self.source {
+            Source::Path(filepath) => Box::new(File::open(filepath.as_str())?),
+            Source::Bytes(bytes) => Box::new(Cursor::new(Arc::clone(bytes))),
+            Source::Reader(reader) => reader.take().ok_or_else(|| {
+                EasyError::Io(std::io::Error::other(
+                    "the reader has already been consumed",
+                ))
+            })?,
+        };
+
+        let mut reader = BufReader::new(reader);
+        let gzip = reader.fill_buf()?.starts_with(&GZIP_MAGIC);
+        if gzip {
+            Ok((Box::new(GzDecoder::new(reader)), true))
         } else {
-            let file = File::open(filepath).map_err(|e| anyhow!(e.to_string()))?;
-            Ok(Box::new(file))
+            Ok((Box::new(reader), false))
         }
     }
 
+    /// Returns the EEG data loaded by [`parse_data`](Self::parse_data), shape `(num_samples, num_channels)`.
+    pub fn eeg(&self) -> Option<ArrayView2<'_, Float>> {
+        self.np_eeg.as_ref().map(|eeg| eeg.view())
+    }
+
+    /// Returns the loaded EEG data for in-place processing, e.g. with
+    /// [`Filter::filtfilt_columns`](crate::filter::Filter::filtfilt_columns).
+    pub fn eeg_mut(&mut self) -> Option<ArrayViewMut2<'_, Float>> {
+        self.np_eeg.as_mut().map(|eeg| eeg.view_mut())
+    }
+
+    /// Returns the accelerometer data, shape `(num_samples, 3)`.
+    ///
+    /// This is `None` until the data is loaded, and for files recorded without accelerometer.
+    pub fn acc(&self) -> Option<ArrayView2<'_, Float>> {
+        self.np_acc.as_ref().map(|acc| acc.view())
+    }
+
+    /// Returns the markers, shape `(num_samples, 1)`.
+    pub fn markers(&self) -> Option<ArrayView2<'_, Float>> {
+        self.np_markers.as_ref().map(|markers| markers.view())
+    }
+
+    /// Returns the time of each sample in seconds relative to the start of the recording,
+    /// shape `(num_samples, 1)`.
+    pub fn time(&self) -> Option<ArrayView2<'_, Float>> {
+        self.np_time.as_ref().map(|time| time.view())
+    }
+
+    /// Returns the absolute time of each sample.
+    pub fn timestamps(&self) -> Option<&[DateTime<Utc>]> {
+        self.timestamps.as_deref()
+    }
+
+    /// Returns the events found in the markers by [`parse_data`](Self::parse_data).
+    ///
+    /// Each run of identical non-zero markers is one event, described by the trigger table of
+    /// the `.info` file.
+    pub fn events(&self) -> Option<&[Event]> {
+        self.events.as_deref()
+    }
+
+    /// Returns a copy of the trigger table of the `.info` file, empty without one.
+    fn triggers(&self) -> HashMap<u32, String> {
+        self.info
+            .as_ref()
+            .map(|info| info.trigger_info.triggers.clone())
+            .unwrap_or_default()
+    }
+
+    /// Returns the names of the EEG channels, in column order.
+    pub fn electrodes(&self) -> &[String] {
+        &self.electrodes
+    }
+
+    /// Returns the path of the `.easy` file, empty when the data does not come from a file.
+    pub fn filepath(&self) -> &str {
+        &self.filepath
+    }
+
+    /// Returns the name of the `.easy` file without its directory and extension.
+    pub fn basename(&self) -> &str {
+        &self.basename
+    }
+
+    /// Returns the number of EEG channels.
+    pub fn num_channels(&self) -> Option<usize> {
+        self.num_channels
+    }
+
+    /// Returns the time of the first sample, once the data has been read.
+    pub fn start_date(&self) -> Option<DateTime<Utc>> {
+        self.eegstartdate
+    }
+
+    /// Returns the column layout of the `.easy` file.
+    pub fn layout(&self) -> Option<Layout> {
+        self.layout
+    }
+
+    /// Returns `true` if the `.easy` file holds accelerometer data.
+    pub fn has_accelerometer(&self) -> bool {
+        self.acc_data
+    }
+
+    /// Returns the number of samples loaded by [`parse_data`](Self::parse_data), or `0` if nothing is loaded.
+    pub fn num_samples(&self) -> usize {
+        self.np_eeg.as_ref().map_or(0, |eeg| eeg.nrows())
+    }
+
+    /// Returns the metadata parsed from the `.info` file, if one was available.
+    pub fn info(&self) -> Option<&EEGData> {
+        self.info.as_ref()
+    }
+
+    /// Returns the EEG sampling rate in Hz declared by the `.info` file.
+    pub fn sampling_rate(&self) -> Option<Float> {
+        self.info
+            .as_ref()
+            .map(|info| info.eeg_settings.sampling_rate as Float)
+            .filter(|rate| *rate > 0.0)
+    }
+
+    /// Returns the unit of the values in `np_eeg`.
+    ///
+    /// `.easy` files store the EEG in the unit declared by the `.info` file, nanovolts if there is
+    /// none. The values are divided by the reader's scale, so a scale of `1000` turns `nV` into `uV`.
+    pub fn eeg_units(&self) -> String {
+        let units = self
+            .info
+            .as_ref()
+            .map(|info| info.eeg_settings.eeg_units.trim())
+            .filter(|units| !units.is_empty())
+            .unwrap_or(DEFAULT_EEG_UNITS);
+        scaled_units(units, self.scale)
+    }
+
+    /// Returns the scale that turns the EEG units declared by the `.info` file, nanovolts if
+    /// there is none, into microvolts.
+    ///
+    /// Units that are not volts keep a scale of `1`.
+    pub fn default_scale(&self) -> Float {
+        let units = self
+            .info
+            .as_ref()
+            .map(|info| info.eeg_settings.eeg_units.trim())
+            .filter(|units| !units.is_empty())
+            .unwrap_or(DEFAULT_EEG_UNITS);
+        const PREFIXES: [(&str, Float); 5] = [
+            ("nV", 1e3),
+            ("uV", 1.0),
+            ("µV", 1.0),
+            ("mV", 1e-3),
+            ("V", 1e-6),
+        ];
+        PREFIXES
+            .iter()
+            .find(|(prefix, _)| *prefix == units)
+            .map_or(1.0, |(_, scale)| *scale)
+    }
+
+    /// Returns the accelerometer units declared by the `.info` file.
+    pub fn acc_units(&self) -> Option<&str> {
+        self.info
+            .as_ref()?
+            .eeg_settings
+            .accelerometer
+            .as_ref()
+            .map(|acc| acc.units.as_str())
+    }
+
+    /// Returns the duration of the loaded data in seconds.
+    ///
+    /// This is the number of samples divided by the sampling rate, the one of the `.info` file
+    /// or else the one estimated from the timestamps, so each sample counts for one period
+    /// whether or not the `.info` file is available. Gaps in the timestamps are not counted.
+    pub fn duration(&self) -> Option<Float> {
+        self.np_eeg.as_ref()?;
+        Some(self.num_samples() as Float / self.estimated_sampling_rate()?)
+    }
+
+    /// Returns the sampling rate of the `.info` file, or estimates it from the timestamps.
+    ///
+    /// The estimate is rounded to the nearest Hz, NIC devices sample at whole rates.
+    pub(crate) fn estimated_sampling_rate(&self) -> Option<Float> {
+        if let Some(rate) = self.sampling_rate() {
+            return Some(rate);
+        }
+        let timestamps = self.timestamps.as_ref()?;
+        let (first, last) = (timestamps.first()?, timestamps.last()?);
+        let span = (*last - *first).num_milliseconds() as Float / 1000.0;
+        (span > 0.0).then(|| ((timestamps.len() - 1) as Float / span).round())
+    }
+
+    /// Takes ownership of the loaded data without copying it.
+    ///
+    /// Returns `None` if [`parse_data`](Self::parse_data) has not been called.
+    pub fn into_parts(self) -> Option<EasyParts> {
+        Some(EasyParts {
+            electrodes: self.electrodes,
+            start_date: self.eegstartdate,
+            eeg: self.np_eeg?,
+            acc: self.np_acc,
+            markers: self.np_markers?,
+            time: self.np_time?,
+            timestamps: self.timestamps?,
+        })
+    }
+
+    /// Returns a copy of the loaded data as a [`Recording`].
+    ///
+    /// The sampling rate is the one of the `.info` file, or is estimated from the timestamps.
+    /// The log of the reader is carried over.
+    pub fn recording(&self) -> Result<Recording> {
+        let parts = EasyParts {
+            electrodes: self.electrodes.clone(),
+            start_date: self.eegstartdate,
+            eeg: self.np_eeg.clone().ok_or_else(not_loaded)?,
+            acc: self.np_acc.clone(),
+            markers: self.np_markers.clone().ok_or_else(not_loaded)?,
+            time: self.np_time.clone().ok_or_else(not_loaded)?,
+            timestamps: self.timestamps.clone().ok_or_else(not_loaded)?,
+        };
+        self.build_recording(parts, self.estimated_sampling_rate())
+    }
+
+    /// Converts the loaded data into a [`Recording`] without copying it.
+    ///
+    /// See [`recording`](Self::recording).
+    pub fn into_recording(mut self) -> Result<Recording> {
+        let rate = self.estimated_sampling_rate();
+        let parts = EasyParts {
+            electrodes: std::mem::take(&mut self.electrodes),
+            start_date: self.eegstartdate,
+            eeg: self.np_eeg.take().ok_or_else(not_loaded)?,
+            acc: self.np_acc.take(),
+            markers: self.np_markers.take().ok_or_else(not_loaded)?,
+            time: self.np_time.take().ok_or_else(not_loaded)?,
+            timestamps: self.timestamps.take().ok_or_else(not_loaded)?,
+        };
+        self.build_recording(parts, rate)
+    }
+
+    fn build_recording(&self, parts: EasyParts, rate: Option<Float>) -> Result<Recording> {
+        let rate = rate.ok_or_else(|| {
+            EasyError::Unsupported("the sampling rate of the recording is unknown".to_string())
+        })?;
+        let info = self.info.clone().unwrap_or_default();
+        let mut recording = Recording::from_parts(parts, info, rate, &self.eeg_units())?;
+        recording.source = self.filepath.clone();
+        recording.log = self.log.clone();
+        recording
+            .log
+            .push(format!("recording created: {}", Utc::now()));
+        Ok(recording)
+    }
+
     /// Prints a summary of the `EasyReader` instance, displaying important metadata and previews of data.
     ///
     /// This function outputs the file path, base name, extension, number of channels, EEG start date,
@@ -479,7 +1254,10 @@ impl EasyReader {
         }
 
         match &self.eegstartdate {
-            Some(start_date) => println!("EEG Start Date: {}", start_date),
+            Some(start_date) => println!(
+                "EEG Start Date: {}",
+                start_date.format("%Y-%m-%d %H:%M:%S%.3f")
+            ),
             None => println!("EEG Start Date: Not available"),
         }
 
@@ -550,3 +1328,338 @@ impl EasyReader {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Helper function to write a small `.easy` file with 8 channels.
+    fn create_sample_file(name: &str, with_acc: bool) -> String {
+        let mut content = String::new();
+        for i in 0..4 {
+            let eeg: Vec<String> = (0..8).map(|c| format!("{}", (i * 10 + c) * 1000)).collect();
+            content.push_str(&eeg.join("\t"));
+            if with_acc {
+                content.push_str(&format!("\t{}\t-{}\t{}", i, i, 9800));
+            }
+            let marker = if i == 2 { 7 } else { 0 };
+            content.push_str(&format!("\t{}\t{}\n", marker, 1609459200000u64 + i * 2));
+        }
+
+        let filename = std::env::temp_dir()
+            .join(format!("{name}.easy"))
+            .to_string_lossy()
+            .to_string();
+        std::fs::write(&filename, content).unwrap();
+        filename
+    }
+
+    #[test]
+    fn test_parse_with_accelerometer() {
+        let filename = create_sample_file("easy_reader_with_acc", true);
+        let mut reader = EasyReader::new(&filename, 1000.0, false).unwrap();
+        reader.parse_data().unwrap();
+
+        assert_eq!(reader.layout, Some(Layout::WithAccelerometer));
+        assert!(reader.acc_data);
+        assert_eq!(reader.num_channels, Some(8));
+
+        let eeg = reader.np_eeg.as_ref().unwrap();
+        assert_eq!(eeg.shape(), &[4, 8]);
+        assert_eq!(eeg[[1, 3]], 13.0);
+
+        let acc = reader.np_acc.as_ref().unwrap();
+        assert_eq!(acc.shape(), &[4, 3]);
+        assert_eq!(acc.row(3).to_vec(), vec![3.0, -3.0, 9800.0]);
+
+        let markers = reader.np_markers.as_ref().unwrap();
+        assert_eq!(markers[[2, 0]], 7.0);
+
+        let time = reader.np_time.as_ref().unwrap();
+        assert_eq!(time.column(0).to_vec(), vec![0.0, 0.002, 0.004, 0.006]);
+
+        let timestamps = reader.timestamps.as_ref().unwrap();
+        assert_eq!(timestamps[3].timestamp_millis(), 1609459200006);
+        assert_eq!(reader.eegstartdate, Some(timestamps[0]));
+
+        std::fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn test_parse_without_accelerometer() {
+        let filename = create_sample_file("easy_reader_without_acc", false);
+        let mut reader = EasyReader::new(&filename, 1000.0, false).unwrap();
+        reader.parse_data().unwrap();
+
+        assert_eq!(reader.layout, Some(Layout::WithoutAccelerometer));
+        assert!(!reader.has_accelerometer());
+        assert!(reader.acc().is_none());
+        assert_eq!(reader.num_samples(), 4);
+        // 4 samples at the 500 Hz estimated from the timestamps, as with a `.info` file
+        assert_eq!(reader.duration(), Some(4.0 / 500.0));
+        assert_eq!(reader.np_eeg.as_ref().unwrap().shape(), &[4, 8]);
+        assert_eq!(reader.np_markers.as_ref().unwrap()[[2, 0]], 7.0);
+
+        let mut markers = Vec::new();
+        let mut time = Vec::new();
+        reader
+            .stream(Some(3), |chunk| {
+                assert!(chunk.acc.is_none());
+                assert_eq!(chunk.eeg.nrows(), chunk.len());
+                markers.extend(chunk.markers.column(0).iter().copied());
+                time.extend(chunk.time.column(0).iter().copied());
+            })
+            .unwrap();
+        assert_eq!(markers, vec![0.0, 0.0, 7.0, 0.0]);
+        assert_eq!(time, vec![0.0, 0.002, 0.004, 0.006]);
+
+        let parts = reader.into_parts().unwrap();
+        assert_eq!(parts.electrodes.len(), 8);
+        assert_eq!(parts.eeg.shape(), &[4, 8]);
+        assert!(parts.acc.is_none());
+
+        std::fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn test_malformed_files() {
+        use std::io::Write;
+
+        let dir = std::env::temp_dir();
+
+        let filename = dir.join("easy_reader_empty.easy");
+        std::fs::write(&filename, "").unwrap();
+        let result = EasyReader::new(filename.to_str().unwrap(), 1.0, false);
+        assert!(matches!(result, Err(EasyError::EmptyFile)));
+        std::fs::remove_file(&filename).unwrap();
+
+        let filename = dir.join("easy_reader_columns.easy");
+        std::fs::write(&filename, "1\t2\t3\n").unwrap();
+        let result = EasyReader::new(filename.to_str().unwrap(), 1.0, false);
+        assert!(matches!(
+            result,
+            Err(EasyError::ColumnCount { line: 1, found: 3 })
+        ));
+        std::fs::remove_file(&filename).unwrap();
+
+        let filename = create_sample_file("easy_reader_truncated", false);
+        let mut content = std::fs::read_to_string(&filename).unwrap();
+        content.push_str("1\t2\tx\t4\t5\t6\t7\t8\t0\t1609459200008\n");
+        content.push_str("1\t2\t3");
+        std::fs::write(&filename, content).unwrap();
+        let mut reader = EasyReader::new(&filename, 1.0, false).unwrap();
+        let result = reader.parse_data();
+        assert!(matches!(
+            result,
+            Err(EasyError::Parse { line: 5, column: 3, ref value }) if value == "x"
+        ));
+        std::fs::remove_file(&filename).unwrap();
+
+        // A gzip stream cut in the middle
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        for i in 0..1000u64 {
+            writeln!(
+                encoder,
+                "{}\t1\t2\t3\t4\t5\t6\t7\t0\t{}",
+                i,
+                1609459200000 + i
+            )
+            .unwrap();
+        }
+        let compressed = encoder.finish().unwrap();
+        let truncated = &compressed[..compressed.len() / 2];
+        let mut reader = EasyReader::from_bytes(truncated, None, 1.0, false).unwrap();
+        assert!(matches!(reader.parse_data(), Err(EasyError::Gzip(_))));
+
+        let result = EasyReader::new("recording.edf", 1.0, false);
+        assert!(matches!(result, Err(EasyError::BadExtension(_))));
+    }
+
+    #[test]
+    fn test_iterators() {
+        let filename = create_sample_file("easy_reader_iterators", true);
+        let mut reader = EasyReader::new(&filename, 1000.0, false).unwrap();
+
+        let samples: Vec<Sample> = reader.samples().unwrap().map(|s| s.unwrap()).collect();
+        assert_eq!(samples.len(), 4);
+        assert_eq!(samples[1].eeg[3], 13.0);
+        assert_eq!(samples[3].acc, Some([3.0, -3.0, 9800.0]));
+        assert_eq!(samples[3].timestamp.timestamp_millis(), 1609459200006);
+
+        let marked = reader
+            .samples()
+            .unwrap()
+            .position(|s| s.unwrap().marker != 0.0);
+        assert_eq!(marked, Some(2));
+
+        let chunks: Vec<Chunk> = reader.chunks(3).unwrap().map(|c| c.unwrap()).collect();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].eeg.shape(), &[3, 8]);
+        assert_eq!(chunks[1].acc.as_ref().unwrap().shape(), &[1, 3]);
+        assert_eq!(chunks[1].time[[0, 0]], 0.006);
+
+        std::fs::remove_file(filename).unwrap();
+
+        // A malformed row in the middle of a chunk ends it, then the error follows
+        let content = "0\t1\t2\t3\t4\t5\t6\t7\t0\t1609459200000\n\
+                       1\t1\t2\t3\t4\t5\t6\t7\t0\t1609459200002\n\
+                       x\t1\t2\t3\t4\t5\t6\t7\t0\t1609459200004\n\
+                       3\t1\t2\t3\t4\t5\t6\t7\t0\t1609459200006\n";
+        let mut reader = EasyReader::from_bytes(content, None, 1.0, false).unwrap();
+        let mut chunks = reader.chunks(3).unwrap();
+        let chunk = chunks.next().unwrap().unwrap();
+        assert_eq!(chunk.eeg.column(0).to_vec(), vec![0.0, 1.0]);
+        assert!(matches!(
+            chunks.next(),
+            Some(Err(EasyError::Parse { line: 3, .. }))
+        ));
+        assert!(chunks.next().is_none());
+    }
+
+    #[test]
+    fn test_read_from_memory() {
+        let filename = create_sample_file("easy_reader_memory", true);
+        let plain = std::fs::read(&filename).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        std::io::Write::write_all(&mut encoder, &plain).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let info = "EEG Settings\nChannel 1: Fp1\nChannel 2: Fp2\nChannel 3: F3\nChannel 4: F4\n\
+                    Channel 5: C3\nChannel 6: C4\nChannel 7: O1\nChannel 8: O2\n";
+
+        for data in [plain.clone(), compressed.clone()] {
+            let mut reader = EasyReader::from_bytes(data, Some(info), 1000.0, false).unwrap();
+            assert_eq!(reader.num_channels(), Some(8));
+            assert_eq!(reader.eeg_units(), "uV");
+            reader.parse_data().unwrap();
+            assert_eq!(reader.electrodes()[7], "O2");
+            assert_eq!(reader.eeg().unwrap()[[1, 3]], 13.0);
+            // In-memory data can be read again
+            assert_eq!(reader.samples().unwrap().count(), 4);
+        }
+
+        let mut reader =
+            EasyReader::from_reader(Cursor::new(compressed), None, 1000.0, false).unwrap();
+        assert_eq!(reader.num_channels(), None);
+        reader.parse_data().unwrap();
+        assert_eq!(reader.num_channels(), Some(8));
+        assert_eq!(reader.acc().unwrap().shape(), &[4, 3]);
+        // A plain reader is consumed by the first pass
+        assert!(matches!(reader.samples(), Err(EasyError::Io(_))));
+    }
+
+    #[test]
+    fn test_info_drives_reader() {
+        let filename = create_sample_file("easy_reader_info", false);
+        let info = "Step Details\nStartDate: 1609459200000\n\nEEG Settings\n\
+                    Number of EEG channels: 8\nEEG sampling rate: 500 Samples/second\n\
+                    EEG units: nV\nAccelerometer data: OFF\n\
+                    Channel 2: Fp2\nChannel 1: Fp1\nChannel 3: F3\nChannel 4: F4\n\
+                    Channel 5: C3\nChannel 6: C4\nChannel 7: O1\nChannel 8: O2\n";
+        std::fs::write(filename.replace(".easy", ".info"), info).unwrap();
+
+        let mut reader = EasyReader::new(&filename, 1.0, false).unwrap();
+        reader.parse_data().unwrap();
+
+        let settings = &reader.info().unwrap().eeg_settings;
+        assert!(settings.accelerometer.is_none());
+        assert_eq!(&reader.electrodes()[..3], &["Fp1", "Fp2", "F3"]);
+        assert_eq!(reader.sampling_rate(), Some(500.0));
+        assert_eq!(reader.eeg_units(), "nV");
+        assert_eq!(reader.duration(), Some(4.0 / 500.0));
+        assert!(!reader.has_accelerometer());
+
+        std::fs::remove_file(filename.replace(".easy", ".info")).unwrap();
+        std::fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn test_info_layout_and_scale() {
+        // 4 channels with accelerometer, a column count no device has
+        let content = "1000\t2000\t3000\t4000\t1\t2\t3\t0\t1609459200000\n\
+                       5000\t6000\t7000\t8000\t4\t5\t6\t0\t1609459200002\n";
+        assert!(matches!(
+            EasyReader::from_bytes(content, None, 1.0, false),
+            Err(EasyError::ColumnCount { found: 9, .. })
+        ));
+
+        let info = "EEG Settings\nEEG units: nV\nAccelerometer data: ON\n\
+                    Channel 1: Fp1\nChannel 2: Fp2\nChannel 3: O1\nChannel 4: O2\n";
+        let mut reader = EasyReader::from_bytes(content, Some(info), 1.0, false).unwrap();
+        assert_eq!(reader.default_scale(), 1000.0);
+        reader.parse_data().unwrap();
+        assert_eq!(reader.layout(), Some(Layout::WithAccelerometer));
+        assert_eq!(reader.eeg().unwrap().dim(), (2, 4));
+        assert_eq!(reader.acc().unwrap().row(1).to_vec(), vec![4.0, 5.0, 6.0]);
+        assert_eq!(reader.acc_units(), Some("mm/s^2"));
+
+        // The scale follows the declared units, files in microvolts are loaded as they are
+        let filename = create_sample_file("easy_reader_open", false);
+        let info_file = filename.replace(".easy", ".info");
+        std::fs::write(&info_file, "EEG Settings\nEEG units: uV\n").unwrap();
+        let mut reader = EasyReader::open(&filename, false).unwrap();
+        reader.parse_data().unwrap();
+        assert_eq!(reader.eeg_units(), "uV");
+        assert_eq!(reader.eeg().unwrap()[[1, 3]], 13000.0);
+
+        std::fs::write(&info_file, "EEG Settings\nEEG units: nV\n").unwrap();
+        let mut reader = EasyReader::open(&filename, false).unwrap();
+        reader.parse_data().unwrap();
+        assert_eq!(reader.eeg_units(), "uV");
+        assert_eq!(reader.eeg().unwrap()[[1, 3]], 13.0);
+
+        std::fs::remove_file(info_file).unwrap();
+        std::fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn test_events() {
+        let mut content = String::new();
+        for (i, marker) in [0, 4, 4, 4, 0, 0, 7, 7, 4, 0].iter().enumerate() {
+            content.push_str(&format!(
+                "1\t2\t3\t4\t5\t6\t7\t8\t{}\t{}\n",
+                marker,
+                1609459200000 + i as u64 * 2
+            ));
+        }
+        let info = "Trigger information\nCode Description\n4 Target\n";
+
+        let mut reader = EasyReader::from_bytes(content, Some(info), 1.0, false).unwrap();
+        assert!(reader.events().is_none());
+        reader.parse_data().unwrap();
+        let events = reader.events().unwrap().to_vec();
+        let summary: Vec<(usize, usize, Float)> = events
+            .iter()
+            .map(|event| (event.sample_index, event.duration, event.code))
+            .collect();
+        assert_eq!(summary, vec![(1, 3, 4.0), (6, 2, 7.0), (8, 1, 4.0)]);
+        assert_eq!(events[0].description.as_deref(), Some("Target"));
+        assert_eq!(events[1].description, None);
+        assert_eq!(events[1].onset_seconds, 0.012);
+        assert_eq!(events[1].timestamp.timestamp_millis(), 1609459200012);
+
+        // A run split between chunks is reported once, where it starts
+        let mut chunk_events = Vec::new();
+        reader
+            .stream(Some(2), |chunk| chunk_events.push(chunk.events))
+            .unwrap();
+        let starts: Vec<Vec<usize>> = chunk_events
+            .iter()
+            .map(|events| events.iter().map(|event| event.sample_index).collect())
+            .collect();
+        assert_eq!(starts, vec![vec![1], vec![], vec![], vec![6], vec![8]]);
+        assert_eq!(chunk_events[0][0].duration, 1);
+        assert_eq!(chunk_events[3][0], events[1]);
+    }
+
+    #[test]
+    fn test_scaled_units() {
+        assert_eq!(scaled_units("nV", 1.0), "nV");
+        assert_eq!(scaled_units("nV", 1000.0), "uV");
+        assert_eq!(scaled_units("µV", 1000.0), "mV");
+        assert_eq!(scaled_units("nV", 1e9), "V");
+        assert_eq!(scaled_units("counts", 2.0), "counts / 2");
+    }
+}
@@ -0,0 +1,405 @@
+use crate::easy_reader::{Chunk, EasyParts, EasyReader, Float, Layout, Sample};
+use crate::error::{EasyError, Result};
+use crate::recording::Recording;
+use chrono::{DateTime, Utc};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use ndarray::{ArrayView2, Axis};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+const DELIMITER: u8 = b'\t';
+
+/// Destination of the rows, compressed or not.
+enum Sink<W: Write> {
+    Plain(W),
+    Gzip(GzEncoder<W>),
+}
+
+impl<W: Write> Write for Sink<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Sink::Plain(writer) => writer.write(buf),
+            Sink::Gzip(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Sink::Plain(writer) => writer.flush(),
+            Sink::Gzip(writer) => writer.flush(),
+        }
+    }
+}
+
+/// Struct writing EEG data in the `.easy` format, optionally gzip-compressed.
+///
+/// Rows are written in the tab-delimited layout read by [`EasyReader::parse_data`]: the EEG
+/// channels, the three accelerometer axes if present, the marker and the Unix timestamp in
+/// milliseconds. EEG values are multiplied by `scale` and rounded to integers, the inverse of
+/// the division applied by [`EasyReader`] with the same scale, so writing `uV` values with a
+/// scale of `1000` stores the integer `nV` NIC tools expect.
+///
+/// The layout is fixed by the first row written: every following row must have the same number
+/// of channels and the same accelerometer presence. Any number of channels can be written, but
+/// only those of NIC devices (8, 20 or 32) are read back without the `.info` file declaring the
+/// montage and the accelerometer, see [`Recording::to_info`]. Call [`finish`](Self::finish) to flush the
+/// data and complete the gzip stream.
+pub struct EasyWriter<W: Write> {
+    writer: csv::Writer<Sink<W>>,
+    scale: Float,
+    layout: Option<(Layout, usize)>,
+    record: Vec<String>,
+}
+
+impl EasyWriter<BufWriter<File>> {
+    /// Creates a `.easy` or `.easy.gz` file, compressing it if the path ends with `.gz`.
+    pub fn create(filepath: &str, scale: Float) -> Result<Self> {
+        if !filepath.ends_with(".easy") && !filepath.ends_with(".easy.gz") {
+            return Err(EasyError::BadExtension(filepath.to_string()));
+        }
+        let file = BufWriter::new(File::create(filepath)?);
+        Ok(Self::new(file, scale, filepath.ends_with(".gz")))
+    }
+}
+
+impl<W: Write> EasyWriter<W> {
+    /// Creates a writer over any destination, gzip-compressing the rows if `gzip` is set.
+    pub fn new(writer: W, scale: Float, gzip: bool) -> Self {
+        let sink = if gzip {
+            Sink::Gzip(GzEncoder::new(writer, Compression::default()))
+        } else {
+            Sink::Plain(writer)
+        };
+
+        EasyWriter {
+            writer: csv::WriterBuilder::new()
+                .delimiter(DELIMITER)
+                .has_headers(false)
+                .from_writer(sink),
+            scale,
+            layout: None,
+            record: Vec::new(),
+        }
+    }
+
+    /// Writes a single sample.
+    pub fn write_sample(&mut self, sample: &Sample) -> Result<()> {
+        self.write_row(
+            sample.eeg.iter().copied(),
+            sample.eeg.len(),
+            sample.acc.as_ref().map(|acc| acc.iter().copied()),
+            sample.marker,
+            sample.timestamp,
+        )
+    }
+
+    /// Writes every sample of a chunk, as yielded by [`EasyReader::chunks`].
+    pub fn write_chunk(&mut self, chunk: &Chunk) -> Result<()> {
+        self.write_arrays(
+            chunk.eeg.view(),
+            chunk.acc.as_ref().map(|acc| acc.view()),
+            chunk.markers.view(),
+            &chunk.timestamps,
+        )
+    }
+
+    /// Writes every sample of a recording taken apart with [`EasyReader::into_parts`].
+    pub fn write_parts(&mut self, parts: &EasyParts) -> Result<()> {
+        self.write_arrays(
+            parts.eeg.view(),
+            parts.acc.as_ref().map(|acc| acc.view()),
+            parts.markers.view(),
+            &parts.timestamps,
+        )
+    }
+
+    /// Writes every sample loaded by [`EasyReader::parse_data`].
+    pub fn write_reader(&mut self, reader: &EasyReader) -> Result<()> {
+        let (Some(eeg), Some(markers), Some(timestamps)) =
+            (reader.eeg(), reader.markers(), reader.timestamps())
+        else {
+            return Err(EasyError::Unsupported(
+                "the reader has no data loaded, call parse_data first".to_string(),
+            ));
+        };
+        self.write_arrays(eeg, reader.acc(), markers, timestamps)
+    }
+
+    /// Writes every sample of a recording.
+    ///
+    /// The accelerometer is written at the EEG rate and the markers are rebuilt from the events.
+    /// Together with the metadata of [`Recording::to_info`] called with the scale of the writer,
+    /// this turns a recording read from any format into a `.easy` and `.info` pair.
+    pub fn write_recording(&mut self, recording: &Recording) -> Result<()> {
+        let acc = recording.accelerometer_at_eeg_rate();
+        let markers = recording.marker_column().insert_axis(Axis(1));
+        self.write_arrays(
+            recording.eeg.view(),
+            acc.as_ref().map(|acc| acc.view()),
+            markers.view(),
+            &recording.timestamps,
+        )
+    }
+
+    /// Writes rows from arrays with one row per sample.
+    fn write_arrays(
+        &mut self,
+        eeg: ArrayView2<Float>,
+        acc: Option<ArrayView2<Float>>,
+        markers: ArrayView2<Float>,
+        timestamps: &[DateTime<Utc>],
+    ) -> Result<()> {
+        let num_samples = eeg.nrows();
+        if markers.nrows() != num_samples
+            || timestamps.len() != num_samples
+            || acc.is_some_and(|acc| acc.nrows() != num_samples || acc.ncols() != 3)
+        {
+            return Err(EasyError::Unsupported(
+                "EEG, accelerometer, markers and timestamps must have one row per sample"
+                    .to_string(),
+            ));
+        }
+
+        for (i, timestamp) in timestamps.iter().enumerate() {
+            self.write_row(
+                eeg.row(i).iter().copied(),
+                eeg.ncols(),
+                acc.as_ref().map(|acc| acc.row(i).into_iter().copied()),
+                markers[[i, 0]],
+                *timestamp,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Formats and writes a single row, checking it against the layout of the first one.
+    fn write_row(
+        &mut self,
+        eeg: impl Iterator<Item = Float>,
+        num_channels: usize,
+        acc: Option<impl Iterator<Item = Float>>,
+        marker: Float,
+        timestamp: DateTime<Utc>,
+    ) -> Result<()> {
+        let layout = if acc.is_some() {
+            Layout::WithAccelerometer
+        } else {
+            Layout::WithoutAccelerometer
+        };
+
+        match self.layout {
+            None => {
+                if num_channels == 0 {
+                    return Err(EasyError::Unsupported(
+                        "rows without EEG channels".to_string(),
+                    ));
+                }
+                self.layout = Some((layout, num_channels));
+            }
+            Some(expected) if expected != (layout, num_channels) => {
+                return Err(EasyError::Unsupported(format!(
+                    "row with {num_channels} channels ({layout:?}) after rows with {} channels ({:?})",
+                    expected.1, expected.0
+                )));
+            }
+            Some(_) => {}
+        }
+
+        self.record.clear();
+        self.record
+            .extend(eeg.map(|value| format_integer(value * self.scale)));
+        if let Some(acc) = acc {
+            self.record.extend(acc.map(format_value));
+        }
+        self.record.push(format_value(marker));
+        self.record.push(timestamp.timestamp_millis().to_string());
+
+        self.writer
+            .write_record(&self.record)
+            .map_err(|e| EasyError::from_csv(e, false))
+    }
+
+    /// Flushes the remaining rows, completes the gzip stream if any, and returns the destination.
+    pub fn finish(self) -> Result<W> {
+        let sink = self
+            .writer
+            .into_inner()
+            .map_err(|e| EasyError::Io(e.into_error()))?;
+        let mut writer = match sink {
+            Sink::Plain(writer) => writer,
+            Sink::Gzip(encoder) => encoder.finish()?,
+        };
+        writer.flush()?;
+        Ok(writer)
+    }
+}
+
+/// Rounds a value to the nearest integer, the way NIC stores EEG samples.
+fn format_integer(value: Float) -> String {
+    format!("{}", value.round() as i64)
+}
+
+/// Formats a value as an integer when it is one, in full precision otherwise.
+fn format_value(value: Float) -> String {
+    if value.fract() == 0.0 && value.abs() < i64::MAX as Float {
+        format!("{}", value as i64)
+    } else {
+        format!("{}", value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::info::EEGData;
+    use ndarray::Array2;
+
+    // Builds a recording with integer nV values, seen in uV with a scale of 1000.
+    fn sample_parts(with_acc: bool) -> EasyParts {
+        let num_samples = 50;
+        let start = DateTime::from_timestamp_millis(1609459200000).unwrap();
+        let timestamps: Vec<DateTime<Utc>> = (0..num_samples)
+            .map(|i| start + chrono::Duration::milliseconds(2 * i as i64))
+            .collect();
+
+        EasyParts {
+            electrodes: (1..=8).map(|x| format!("Ch{}", x)).collect(),
+            start_date: Some(start),
+            eeg: Array2::from_shape_fn((num_samples, 8), |(i, c)| {
+                ((i as i64 * 7919 + c as i64 * 104729) % 200001 - 100000) as Float / 1000.0
+            }),
+            acc: with_acc.then(|| {
+                Array2::from_shape_fn((num_samples, 3), |(i, c)| (i as Float - 25.0) * c as Float)
+            }),
+            markers: Array2::from_shape_fn((num_samples, 1), |(i, _)| {
+                if i % 10 == 3 {
+                    (i / 10 + 1) as Float
+                } else {
+                    0.0
+                }
+            }),
+            time: Array2::from_shape_fn((num_samples, 1), |(i, _)| 2.0 * i as Float / 1000.0),
+            timestamps,
+        }
+    }
+
+    fn round_trip(parts: &EasyParts, gzip: bool) -> EasyParts {
+        let mut writer = EasyWriter::new(Vec::new(), 1000.0, gzip);
+        writer.write_parts(parts).unwrap();
+        let bytes = writer.finish().unwrap();
+
+        let mut reader = EasyReader::from_bytes(bytes, None, 1000.0, false).unwrap();
+        reader.parse_data().unwrap();
+        reader.into_parts().unwrap()
+    }
+
+    fn assert_same(read: &EasyParts, written: &EasyParts) {
+        assert_eq!(read.eeg, written.eeg);
+        assert_eq!(read.acc, written.acc);
+        assert_eq!(read.markers, written.markers);
+        assert_eq!(read.time, written.time);
+        assert_eq!(read.timestamps, written.timestamps);
+        assert_eq!(read.start_date, written.start_date);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for with_acc in [true, false] {
+            for gzip in [false, true] {
+                let parts = sample_parts(with_acc);
+                assert_same(&round_trip(&parts, gzip), &parts);
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip_file_and_stream() {
+        let parts = sample_parts(true);
+        let filename = std::env::temp_dir()
+            .join("easy_writer_round_trip.easy.gz")
+            .to_string_lossy()
+            .to_string();
+
+        let mut writer = EasyWriter::create(&filename, 1000.0).unwrap();
+        writer.write_parts(&parts).unwrap();
+        writer.finish().unwrap();
+
+        // Copy the file chunk by chunk, then sample by sample
+        let mut reader = EasyReader::new(&filename, 1000.0, false).unwrap();
+        let mut chunk_writer = EasyWriter::new(Vec::new(), 1000.0, false);
+        for chunk in reader.chunks(7).unwrap() {
+            chunk_writer.write_chunk(&chunk.unwrap()).unwrap();
+        }
+        let mut sample_writer = EasyWriter::new(Vec::new(), 1000.0, false);
+        for sample in reader.samples().unwrap() {
+            sample_writer.write_sample(&sample.unwrap()).unwrap();
+        }
+        let chunk_bytes = chunk_writer.finish().unwrap();
+        assert_eq!(chunk_bytes, sample_writer.finish().unwrap());
+
+        reader.parse_data().unwrap();
+        assert_same(&reader.into_parts().unwrap(), &parts);
+
+        let mut reader = EasyReader::from_bytes(chunk_bytes, None, 1000.0, false).unwrap();
+        reader.parse_data().unwrap();
+        assert_same(&reader.into_parts().unwrap(), &parts);
+
+        std::fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn test_round_trip_any_channel_count() {
+        // 19 channels of the 10-20 system, read back through the montage of the `.info` file
+        let parts = sample_parts(true);
+        let eeg = Array2::from_shape_fn((parts.timestamps.len(), 19), |(i, c)| {
+            ((i as i64 * 7919 + c as i64 * 104729) % 200001 - 100000) as Float / 1000.0
+        });
+        let recording = Recording::from_parts(
+            EasyParts {
+                electrodes: (1..=19).map(|x| format!("E{}", x)).collect(),
+                eeg,
+                ..parts
+            },
+            EEGData::default(),
+            500.0,
+            "uV",
+        )
+        .unwrap();
+
+        let mut writer = EasyWriter::new(Vec::new(), 1000.0, false);
+        writer.write_recording(&recording).unwrap();
+        let bytes = writer.finish().unwrap();
+        let info = recording.to_info(1000.0).to_string();
+
+        let mut reader = EasyReader::from_bytes(bytes, Some(&info), 1000.0, false).unwrap();
+        reader.parse_data().unwrap();
+        let read = reader.into_recording().unwrap();
+        assert_eq!(read.num_channels(), 19);
+        assert_eq!(read.channels[18].name, "E19");
+        assert_eq!(read.eeg, recording.eeg);
+        assert_eq!(read.marker_column(), recording.marker_column());
+        assert_eq!(
+            read.accelerometer.map(|acc| acc.data),
+            recording.accelerometer.map(|acc| acc.data)
+        );
+    }
+
+    #[test]
+    fn test_rejects_unreadable_layouts() {
+        let mut parts = sample_parts(false);
+        parts.eeg = Array2::zeros((parts.timestamps.len(), 0));
+        let mut writer = EasyWriter::new(Vec::new(), 1.0, false);
+        assert!(matches!(
+            writer.write_parts(&parts),
+            Err(EasyError::Unsupported(_))
+        ));
+
+        let mut writer = EasyWriter::new(Vec::new(), 1.0, false);
+        writer.write_parts(&sample_parts(false)).unwrap();
+        assert!(matches!(
+            writer.write_parts(&sample_parts(true)),
+            Err(EasyError::Unsupported(_))
+        ));
+    }
+}
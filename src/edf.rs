@@ -0,0 +1,1347 @@
+use crate::easy_reader::{scaled_units, shift_prefix, EasyParts, Float};
+use crate::error::{EasyError, Result};
+use crate::info::{AccelerometerData, EEGData};
+use crate::recording::Recording;
+use chrono::{DateTime, Datelike, TimeZone, Timelike, Utc};
+use ndarray::{Array1, Array2, ArrayView1};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+
+/// Duration of a data record in seconds.
+const RECORD_DURATION: usize = 1;
+
+/// Description of the annotation marking the padding of the last data record.
+const PADDING_ANNOTATION: &str = "Padding";
+
+/// Flavour of the European Data Format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdfFormat {
+    /// EDF/EDF+, 16-bit samples.
+    Edf,
+    /// BioSemi BDF/BDF+, 24-bit samples.
+    Bdf,
+}
+
+impl EdfFormat {
+    /// Number of bytes of each sample.
+    fn sample_bytes(&self) -> usize {
+        match self {
+            EdfFormat::Edf => 2,
+            EdfFormat::Bdf => 3,
+        }
+    }
+
+    /// Lowest and highest digital value of a sample.
+    fn digital_range(&self) -> (i32, i32) {
+        match self {
+            EdfFormat::Edf => (-32768, 32767),
+            EdfFormat::Bdf => (-8388608, 8388607),
+        }
+    }
+
+    /// Content of the version field.
+    fn version(&self) -> &'static [u8] {
+        match self {
+            EdfFormat::Edf => b"0",
+            EdfFormat::Bdf => b"\xffBIOSEMI",
+        }
+    }
+
+    /// Content of the reserved field, which tells the `+` variants apart.
+    fn reserved(&self, annotations: bool) -> &'static str {
+        match (self, annotations) {
+            (EdfFormat::Edf, true) => "EDF+C",
+            (EdfFormat::Edf, false) => "",
+            (EdfFormat::Bdf, true) => "BDF+C",
+            (EdfFormat::Bdf, false) => "24BIT",
+        }
+    }
+
+    /// Label of the annotations signal.
+    fn annotations_label(&self) -> &'static str {
+        match self {
+            EdfFormat::Edf => "EDF Annotations",
+            EdfFormat::Bdf => "BDF Annotations",
+        }
+    }
+}
+
+/// Signals written by [`write`] besides the EEG channels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EdfOptions {
+    /// Sample width of the file.
+    pub format: EdfFormat,
+    /// Adds the X, Y and Z accelerometer axes as extra signals, when the recording has them.
+    pub accelerometer: bool,
+    /// Adds a `Status` signal holding the marker code of every sample.
+    pub status_channel: bool,
+    /// Adds the markers as an annotations signal, making the file EDF+/BDF+.
+    pub annotations: bool,
+}
+
+impl EdfOptions {
+    /// EDF+ with the EEG channels and the markers as annotations.
+    pub fn edf() -> Self {
+        EdfOptions {
+            format: EdfFormat::Edf,
+            accelerometer: false,
+            status_channel: false,
+            annotations: true,
+        }
+    }
+
+    /// BDF+ with the EEG channels, the accelerometer axes, a status channel and annotations.
+    pub fn bdf() -> Self {
+        EdfOptions {
+            format: EdfFormat::Bdf,
+            accelerometer: true,
+            status_channel: true,
+            annotations: true,
+        }
+    }
+}
+
+/// Samples of a signal.
+enum SignalData<'a> {
+    /// One value per sample of the recording, converted to the digital range.
+    Samples(ArrayView1<'a, Float>),
+    /// Raw annotation bytes of each data record.
+    Annotations(Vec<Vec<u8>>),
+}
+
+/// A signal to be written in the data records.
+struct Signal<'a> {
+    label: String,
+    physical_dimension: String,
+    physical_min: Float,
+    physical_max: Float,
+    digital_min: i32,
+    digital_max: i32,
+    samples_per_record: usize,
+    /// Factor from the values of `data` to the physical dimension.
+    factor: Float,
+    /// Pads the last data record with the last value rather than with zeros.
+    hold_last: bool,
+    data: SignalData<'a>,
+}
+
+impl<'a> Signal<'a> {
+    /// Creates a signal whose physical range covers its data.
+    ///
+    /// Voltages too large for the 8 characters of the header range are written with a larger
+    /// prefix, e.g. `mV` rather than `uV`; other values too large are an error.
+    fn new(
+        label: String,
+        physical_dimension: &str,
+        data: ArrayView1<'a, Float>,
+        format: EdfFormat,
+        samples_per_record: usize,
+    ) -> Result<Self> {
+        let (min, max) = data
+            .iter()
+            .fold((Float::INFINITY, Float::NEG_INFINITY), |(min, max), x| {
+                (min.min(*x), max.max(*x))
+            });
+        let (min, max) = if min.is_finite() && max > min {
+            (min, max)
+        } else {
+            let center = if min.is_finite() { min } else { 0.0 };
+            (center - 1.0, center + 1.0)
+        };
+
+        // The header stores the range with 8 characters, scale the samples with the stored values
+        let mut physical_dimension = ascii_units(physical_dimension);
+        let mut factor = 1.0;
+        let (physical_min, physical_max) = loop {
+            let range = (
+                format_header_number((min * factor).floor(), 8),
+                format_header_number((max * factor).ceil(), 8),
+            );
+            if let (Some(min), Some(max)) = range {
+                break (parse_header_number(&min), parse_header_number(&max));
+            }
+            physical_dimension = shift_prefix(&physical_dimension, 1000.0).ok_or_else(|| {
+                EasyError::Unsupported(format!(
+                    "the range of {} from {} to {} {} does not fit in an EDF header",
+                    label, min, max, physical_dimension
+                ))
+            })?;
+            factor /= 1000.0;
+        };
+        let (digital_min, digital_max) = format.digital_range();
+
+        Ok(Signal {
+            label,
+            physical_dimension,
+            physical_min,
+            physical_max,
+            digital_min,
+            digital_max,
+            samples_per_record,
+            factor,
+            hold_last: true,
+            data: SignalData::Samples(data),
+        })
+    }
+
+    /// Creates a signal whose values are stored as they are, e.g. trigger codes.
+    fn status(
+        label: &str,
+        data: ArrayView1<'a, Float>,
+        format: EdfFormat,
+        samples_per_record: usize,
+    ) -> Self {
+        let (digital_min, digital_max) = format.digital_range();
+        Signal {
+            label: label.to_string(),
+            physical_dimension: "Boolean".to_string(),
+            physical_min: digital_min as Float,
+            physical_max: digital_max as Float,
+            digital_min,
+            digital_max,
+            samples_per_record,
+            factor: 1.0,
+            hold_last: false,
+            data: SignalData::Samples(data),
+        }
+    }
+
+    /// Creates the annotations signal from the bytes of each data record.
+    fn annotations(record_tals: Vec<Vec<u8>>, format: EdfFormat) -> Self {
+        let (digital_min, digital_max) = format.digital_range();
+        let samples_per_record = record_tals
+            .iter()
+            .map(|tal| tal.len().div_ceil(format.sample_bytes()))
+            .max()
+            .unwrap_or(0);
+
+        Signal {
+            label: format.annotations_label().to_string(),
+            physical_dimension: String::new(),
+            physical_min: -1.0,
+            physical_max: 1.0,
+            digital_min,
+            digital_max,
+            samples_per_record,
+            factor: 1.0,
+            hold_last: false,
+            data: SignalData::Annotations(record_tals),
+        }
+    }
+
+    /// Converts a physical value into the digital range.
+    fn digital(&self, value: Float) -> i32 {
+        let physical_span = self.physical_max - self.physical_min;
+        let digital_span = (self.digital_max - self.digital_min) as Float;
+        let value = value * self.factor;
+        let scaled =
+            (value - self.physical_min) / physical_span * digital_span + self.digital_min as Float;
+        (scaled.round() as i32).clamp(self.digital_min, self.digital_max)
+    }
+
+    /// Appends the samples of a data record.
+    fn write_record(&self, record: usize, format: EdfFormat, bytes: &mut Vec<u8>) {
+        let sample_bytes = format.sample_bytes();
+        match &self.data {
+            SignalData::Samples(data) => {
+                let first = record * self.samples_per_record;
+                for i in first..first + self.samples_per_record {
+                    // The last record is padded without a step, or with no code for markers
+                    let value = match (data.get(i), data.iter().last()) {
+                        (Some(value), _) => *value,
+                        (None, Some(last)) if self.hold_last => *last,
+                        _ => 0.0,
+                    };
+                    let value = self.digital(value);
+                    // Little-endian two's complement, truncated to the sample width
+                    bytes.extend_from_slice(&value.to_le_bytes()[..sample_bytes]);
+                }
+            }
+            SignalData::Annotations(record_tals) => {
+                let tals = &record_tals[record];
+                bytes.extend_from_slice(tals);
+                bytes.resize(
+                    bytes.len() + self.samples_per_record * sample_bytes - tals.len(),
+                    0,
+                );
+            }
+        }
+    }
+}
+
+/// A marker to be written as an EDF+ annotation.
+struct Annotation {
+    onset: Float,
+    description: String,
+}
+
+/// Writes a recording as an EDF+ file.
+///
+/// See [`write_to`] for the details of the conversion.
+pub fn write_edf(recording: &Recording, filename: &str) -> Result<()> {
+    write(recording, filename, &EdfOptions::edf())
+}
+
+/// Writes a recording in the EDF+ format to any writer.
+pub fn write_edf_to<W: Write>(recording: &Recording, writer: &mut W) -> Result<()> {
+    write_to(recording, writer, &EdfOptions::edf())
+}
+
+/// Writes a recording as a BDF+ file, with accelerometer and status channels.
+///
+/// See [`write_to`] for the details of the conversion.
+pub fn write_bdf(recording: &Recording, filename: &str) -> Result<()> {
+    write(recording, filename, &EdfOptions::bdf())
+}
+
+/// Writes a recording in the BDF+ format to any writer.
+pub fn write_bdf_to<W: Write>(recording: &Recording, writer: &mut W) -> Result<()> {
+    write_to(recording, writer, &EdfOptions::bdf())
+}
+
+/// Writes a recording as an EDF or BDF file.
+pub fn write(recording: &Recording, filename: &str, options: &EdfOptions) -> Result<()> {
+    let mut file = BufWriter::new(File::create(filename)?);
+    write_to(recording, &mut file, options)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Writes a recording in the EDF or BDF format to any writer.
+///
+/// Each EEG channel becomes a signal labelled `EEG <electrode>`, in the unit of the channel,
+/// with a physical range covering its data: 16-bit samples quantize it in 65536 steps, 24-bit
+/// BDF samples in 16777216 steps. A range too wide for the header is written with a larger
+/// prefix of volts, e.g. `mV` for a channel in `uV`. Data records last one second, so the sampling rate must be a
+/// whole number of Hz.
+///
+/// Depending on `options`, the accelerometer axes are added as `Accel X/Y/Z` signals at the
+/// EEG rate, the marker codes as a `Status` signal, and the events as annotations described by
+/// the trigger table or by their code.
+///
+/// The samples of the last data record past the end of the recording repeat the last value,
+/// with no code in the `Status` signal. With annotations, a `Padding` annotation spans them.
+pub fn write_to<W: Write>(
+    recording: &Recording,
+    writer: &mut W,
+    options: &EdfOptions,
+) -> Result<()> {
+    let format = options.format;
+    let sampling_rate = sampling_rate(recording)?;
+    let samples_per_record = sampling_rate as usize * RECORD_DURATION;
+    let num_samples = recording.num_samples();
+    let num_records = num_samples.div_ceil(samples_per_record).max(1);
+
+    let markers = recording.marker_column();
+    let acc = recording
+        .accelerometer_at_eeg_rate()
+        .filter(|_| options.accelerometer);
+    let mut signals = data_signals(
+        recording,
+        &markers,
+        acc.as_ref(),
+        options,
+        samples_per_record,
+    )?;
+
+    if options.annotations {
+        signals.push(Signal::annotations(
+            annotation_records(recording, num_records),
+            format,
+        ));
+    }
+
+    write_header(
+        writer,
+        format,
+        options.annotations,
+        recording.start_time,
+        &signals,
+        num_records,
+    )?;
+
+    let record_bytes: usize = signals
+        .iter()
+        .map(|signal| signal.samples_per_record * format.sample_bytes())
+        .sum();
+    let mut bytes = Vec::with_capacity(record_bytes);
+    for record in 0..num_records {
+        bytes.clear();
+        for signal in &signals {
+            signal.write_record(record, format, &mut bytes);
+        }
+        writer.write_all(&bytes)?;
+    }
+
+    Ok(())
+}
+
+/// Label and physical dimension of each signal [`write`] stores besides the annotations.
+///
+/// Voltages may be written with a larger prefix than the unit of their channel, see
+/// [`Signal::new`].
+pub(crate) fn signal_units(
+    recording: &Recording,
+    options: &EdfOptions,
+) -> Result<Vec<(String, String)>> {
+    let samples_per_record = sampling_rate(recording)? as usize * RECORD_DURATION;
+    let markers = recording.marker_column();
+    let acc = recording
+        .accelerometer_at_eeg_rate()
+        .filter(|_| options.accelerometer);
+    Ok(data_signals(
+        recording,
+        &markers,
+        acc.as_ref(),
+        options,
+        samples_per_record,
+    )?
+    .into_iter()
+    .map(|signal| (signal.label, signal.physical_dimension))
+    .collect())
+}
+
+/// Creates the EEG, accelerometer and `Status` signals of a recording.
+fn data_signals<'a>(
+    recording: &'a Recording,
+    markers: &'a Array1<Float>,
+    acc: Option<&'a Array2<Float>>,
+    options: &EdfOptions,
+    samples_per_record: usize,
+) -> Result<Vec<Signal<'a>>> {
+    let format = options.format;
+    let mut signals: Vec<Signal> = recording
+        .channels
+        .iter()
+        .zip(recording.eeg.columns())
+        .map(|(channel, data)| {
+            Signal::new(
+                format!("EEG {}", channel.name),
+                &channel.unit,
+                data,
+                format,
+                samples_per_record,
+            )
+        })
+        .collect::<Result<_>>()?;
+
+    if let (Some(acc), Some(accelerometer)) = (acc, &recording.accelerometer) {
+        for (channel, data) in accelerometer.channels.iter().zip(acc.columns()) {
+            signals.push(Signal::new(
+                format!("Accel {}", channel.name),
+                &channel.unit,
+                data,
+                format,
+                samples_per_record,
+            )?);
+        }
+    }
+
+    if options.status_channel {
+        signals.push(Signal::status(
+            "Status",
+            markers.view(),
+            format,
+            samples_per_record,
+        ));
+    }
+
+    Ok(signals)
+}
+
+/// Encodes the events as the annotation bytes of each data record.
+fn annotation_records(recording: &Recording, num_records: usize) -> Vec<Vec<u8>> {
+    // EDF start times have a resolution of one second, the remainder shifts every onset
+    let start_offset = recording.start_time.timestamp_subsec_millis() as Float / 1000.0;
+    let annotations: Vec<Annotation> = recording
+        .events
+        .iter()
+        .map(|event| Annotation {
+            onset: start_offset + event.sample_index as Float / recording.sampling_rate,
+            description: event.label(),
+        })
+        .collect();
+
+    // Every data record starts with a time-keeping annotation
+    let mut record_tals: Vec<Vec<u8>> = (0..num_records)
+        .map(|record| tal(start_offset + (record * RECORD_DURATION) as Float, None, ""))
+        .collect();
+    for annotation in &annotations {
+        let record = ((annotation.onset - start_offset) / RECORD_DURATION as Float) as usize;
+        record_tals[record.min(num_records - 1)].extend(tal(
+            annotation.onset,
+            None,
+            &annotation.description,
+        ));
+    }
+
+    // The samples past the end of the recording are padding
+    let duration = recording.num_samples() as Float / recording.sampling_rate;
+    let padding = (num_records * RECORD_DURATION) as Float - duration;
+    if padding > 0.0 {
+        record_tals[num_records - 1].extend(tal(
+            start_offset + duration,
+            Some(padding),
+            PADDING_ANNOTATION,
+        ));
+    }
+
+    record_tals
+}
+
+/// Returns the sampling rate, which must give a whole number of samples per data record.
+fn sampling_rate(recording: &Recording) -> Result<Float> {
+    let rate = recording.sampling_rate;
+    if rate.fract() != 0.0 || rate <= 0.0 {
+        return Err(EasyError::Unsupported(format!(
+            "EDF data records need a whole number of samples per second, got {} Hz",
+            rate
+        )));
+    }
+    Ok(rate)
+}
+
+/// Writes the fixed header followed by the header of each signal.
+fn write_header<W: Write>(
+    writer: &mut W,
+    format: EdfFormat,
+    annotations: bool,
+    start_date: DateTime<Utc>,
+    signals: &[Signal],
+    num_records: usize,
+) -> Result<()> {
+    const MONTHS: [&str; 12] = [
+        "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+    ];
+
+    let num_signals = signals.len();
+    let mut header = Vec::with_capacity(256 * (num_signals + 1));
+
+    // The BDF version field starts with a non-ASCII byte
+    let version = format.version();
+    header.extend_from_slice(version);
+    push_field(&mut header, "", 8 - version.len());
+    push_field(&mut header, "X X X X", 80);
+    push_field(
+        &mut header,
+        &format!(
+            "Startdate {:02}-{}-{} X X X",
+            start_date.day(),
+            MONTHS[start_date.month0() as usize],
+            start_date.year()
+        ),
+        80,
+    );
+    push_field(&mut header, &start_date.format("%d.%m.%y").to_string(), 8);
+    push_field(
+        &mut header,
+        &format!(
+            "{:02}.{:02}.{:02}",
+            start_date.hour(),
+            start_date.minute(),
+            start_date.second()
+        ),
+        8,
+    );
+    push_field(&mut header, &(256 * (num_signals + 1)).to_string(), 8);
+    push_field(&mut header, format.reserved(annotations), 44);
+    push_field(&mut header, &num_records.to_string(), 8);
+    push_field(&mut header, &RECORD_DURATION.to_string(), 8);
+    push_field(&mut header, &num_signals.to_string(), 4);
+
+    // Each signal field is written for all signals before the next field
+    for signal in signals {
+        push_field(&mut header, &signal.label, 16);
+    }
+    for _ in signals {
+        push_field(&mut header, "", 80);
+    }
+    for signal in signals {
+        push_field(&mut header, &signal.physical_dimension, 8);
+    }
+    // Every range was checked to fit when the signals were created
+    for signal in signals {
+        push_field(
+            &mut header,
+            &format_header_number(signal.physical_min, 8).unwrap_or_default(),
+            8,
+        );
+    }
+    for signal in signals {
+        push_field(
+            &mut header,
+            &format_header_number(signal.physical_max, 8).unwrap_or_default(),
+            8,
+        );
+    }
+    for signal in signals {
+        push_field(&mut header, &signal.digital_min.to_string(), 8);
+    }
+    for signal in signals {
+        push_field(&mut header, &signal.digital_max.to_string(), 8);
+    }
+    for _ in signals {
+        push_field(&mut header, "", 80);
+    }
+    for signal in signals {
+        push_field(&mut header, &signal.samples_per_record.to_string(), 8);
+    }
+    for _ in signals {
+        push_field(&mut header, "", 32);
+    }
+
+    writer.write_all(&header)?;
+    Ok(())
+}
+
+/// A signal described in the header of a file being read.
+struct SignalHeader {
+    label: String,
+    physical_dimension: String,
+    physical_min: Float,
+    physical_max: Float,
+    digital_min: i32,
+    digital_max: i32,
+    samples_per_record: usize,
+    /// Offset of the samples of the signal within a data record, in samples.
+    offset: usize,
+}
+
+impl SignalHeader {
+    /// Converts a digital value into the physical range.
+    fn physical(&self, digital: i32) -> Float {
+        let digital_span = (self.digital_max - self.digital_min) as Float;
+        if digital_span == 0.0 {
+            return digital as Float;
+        }
+        self.physical_min
+            + (digital - self.digital_min) as Float / digital_span
+                * (self.physical_max - self.physical_min)
+    }
+
+    fn is_annotations(&self) -> bool {
+        self.label == EdfFormat::Edf.annotations_label()
+            || self.label == EdfFormat::Bdf.annotations_label()
+    }
+}
+
+/// Reads an EDF, EDF+, BDF or BDF+ file.
+///
+/// See [`read_edf_from`].
+pub fn read_edf(filename: &str, scale: Float) -> Result<Recording> {
+    let mut recording = read_edf_from(BufReader::new(File::open(filename)?), scale)?;
+    recording.source = filename.to_string();
+    Ok(recording)
+}
+
+/// Reads an EDF, EDF+, BDF or BDF+ file from any reader.
+///
+/// Every ordinary signal sampled at the rate of the first one becomes an EEG channel, named
+/// after its label without the `EEG ` prefix; signals at other rates are left out. The
+/// `Accel X/Y/Z` and `Status` signals written by [`write_bdf`] become the accelerometer and
+/// the markers. Otherwise the markers come from the annotations: each one marks the sample at
+/// its onset with its description when it is a number, or with a code allocated in the
+/// trigger table of the recording.
+///
+/// Voltages are converted to nanovolts and divided by `scale`, like `.easy` data. The
+/// timestamps are synthesized with millisecond resolution from the start time of the header
+/// and the onset of each data record. The samples spanned by the `Padding` annotation that
+/// [`write_to`] adds to the last record are left out; without it, the last record is read
+/// whole.
+pub fn read_edf_from<R: Read>(mut reader: R, scale: Float) -> Result<Recording> {
+    let mut header = vec![0u8; 256];
+    reader.read_exact(&mut header)?;
+    let format = if header.starts_with(EdfFormat::Bdf.version()) {
+        EdfFormat::Bdf
+    } else if header_field(&header, 0, 8) == "0" {
+        EdfFormat::Edf
+    } else {
+        return Err(EasyError::Header(
+            "not an EDF or BDF file, unknown version".to_string(),
+        ));
+    };
+
+    let number = |header: &[u8], offset: usize, width: usize, name: &str| -> Result<Float> {
+        let value = header_field(header, offset, width);
+        value.parse().map_err(|_| {
+            EasyError::Header(format!("invalid {} in the EDF header: {:?}", name, value))
+        })
+    };
+    let header_start = parse_start_date(
+        &header_field(&header, 168, 8),
+        &header_field(&header, 176, 8),
+    )?;
+    let header_bytes = number(&header, 184, 8, "header size")? as usize;
+    let declared_records = number(&header, 236, 8, "number of data records")?;
+    let record_duration = number(&header, 244, 8, "data record duration")?;
+    let num_signals = number(&header, 252, 4, "number of signals")? as usize;
+    if header_bytes != 256 * (num_signals + 1) {
+        return Err(EasyError::Header(format!(
+            "{} header bytes declared for {} signals",
+            header_bytes, num_signals
+        )));
+    }
+
+    // Each field is stored for all signals before the next field
+    let mut header = vec![0u8; 256 * num_signals];
+    reader.read_exact(&mut header)?;
+    let ns = num_signals;
+    let mut signals = Vec::with_capacity(ns);
+    let mut offset = 0;
+    for i in 0..ns {
+        let samples_per_record = number(&header, ns * 216 + i * 8, 8, "number of samples")?;
+        let signal = SignalHeader {
+            label: header_field(&header, i * 16, 16),
+            physical_dimension: header_field(&header, ns * 96 + i * 8, 8),
+            physical_min: number(&header, ns * 104 + i * 8, 8, "physical minimum")?,
+            physical_max: number(&header, ns * 112 + i * 8, 8, "physical maximum")?,
+            digital_min: number(&header, ns * 120 + i * 8, 8, "digital minimum")? as i32,
+            digital_max: number(&header, ns * 128 + i * 8, 8, "digital maximum")? as i32,
+            samples_per_record: samples_per_record as usize,
+            offset,
+        };
+        offset += signal.samples_per_record;
+        signals.push(signal);
+    }
+    let samples_per_record = offset;
+
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let sample_bytes = format.sample_bytes();
+    let record_bytes = samples_per_record * sample_bytes;
+    let num_records = if declared_records >= 0.0 {
+        declared_records as usize
+    } else {
+        data.len() / record_bytes.max(1)
+    };
+    if data.len() < num_records * record_bytes {
+        return Err(EasyError::Header(format!(
+            "{} data records declared, the file is truncated",
+            num_records
+        )));
+    }
+    let digital = |record: usize, signal: &SignalHeader, sample: usize| -> i32 {
+        let start = record * record_bytes + (signal.offset + sample) * sample_bytes;
+        let mut bytes = [0u8; 4];
+        bytes[4 - sample_bytes..].copy_from_slice(&data[start..start + sample_bytes]);
+        // Little-endian two's complement, sign-extended from the sample width
+        i32::from_le_bytes(bytes) >> (8 * (4 - sample_bytes))
+    };
+
+    // Sort the signals out
+    let annotations = signals.iter().find(|signal| signal.is_annotations());
+    let status = signals.iter().find(|signal| signal.label == "Status");
+    let acc: Vec<&SignalHeader> = ["Accel X", "Accel Y", "Accel Z"]
+        .iter()
+        .filter_map(|label| signals.iter().find(|signal| signal.label == *label))
+        .collect();
+    let ordinary: Vec<&SignalHeader> = signals
+        .iter()
+        .filter(|signal| {
+            !signal.is_annotations()
+                && signal.label != "Status"
+                && !signal.label.starts_with("Accel ")
+        })
+        .collect();
+    let rate_samples = match ordinary.first() {
+        Some(signal) if signal.samples_per_record > 0 && record_duration > 0.0 => {
+            signal.samples_per_record
+        }
+        _ => return Err(EasyError::Header("the file has no data signal".to_string())),
+    };
+    let eeg_signals: Vec<&SignalHeader> = ordinary
+        .into_iter()
+        .filter(|signal| signal.samples_per_record == rate_samples)
+        .collect();
+    let status = status.filter(|signal| signal.samples_per_record == rate_samples);
+    let acc = if acc.len() == 3
+        && acc
+            .iter()
+            .all(|signal| signal.samples_per_record == rate_samples)
+    {
+        acc
+    } else {
+        Vec::new()
+    };
+    let sampling_rate = rate_samples as Float / record_duration;
+
+    // Onset of each data record, from the time-keeping annotations of EDF+ and BDF+
+    let mut record_onsets: Vec<Float> = (0..num_records)
+        .map(|record| record as Float * record_duration)
+        .collect();
+    let mut events: Vec<(Float, String)> = Vec::new();
+    let mut padding_onset = None;
+    if let Some(signal) = annotations {
+        for (record, record_onset) in record_onsets.iter_mut().enumerate() {
+            let start = record * record_bytes + signal.offset * sample_bytes;
+            let bytes = &data[start..start + signal.samples_per_record * sample_bytes];
+            for (i, (onset, duration, descriptions)) in parse_tals(bytes).into_iter().enumerate() {
+                if i == 0 && descriptions.is_empty() {
+                    *record_onset = onset;
+                }
+                for description in descriptions {
+                    // The padding of the last record written by `write_to` is left out, starting
+                    // in that record and running to its end
+                    let end = *record_onset + record_duration;
+                    let tolerance = 0.5 / rate_samples as Float * record_duration;
+                    if record + 1 == num_records
+                        && description == PADDING_ANNOTATION
+                        && onset >= *record_onset
+                        && onset < end
+                        && duration
+                            .is_some_and(|duration| (onset + duration - end).abs() <= tolerance)
+                    {
+                        padding_onset = Some(onset);
+                    } else {
+                        events.push((onset, description));
+                    }
+                }
+            }
+        }
+    }
+
+    // Index of the sample at an onset, events are placed in the record they fall in since
+    // records may be discontinuous
+    let sample_index = |onset: Float| -> usize {
+        let record = record_onsets
+            .iter()
+            .rposition(|record_onset| *record_onset <= onset)
+            .unwrap_or(0);
+        let sample = ((onset - record_onsets[record]) * sampling_rate).round() as usize;
+        record * rate_samples + sample.min(rate_samples - 1)
+    };
+
+    let num_samples = match padding_onset {
+        Some(onset) => sample_index(onset).min(num_records * rate_samples),
+        None => num_records * rate_samples,
+    };
+    let num_channels = eeg_signals.len();
+    // Signals in volts may differ in prefix, they are all converted to nanovolts
+    let factors: Vec<Option<Float>> = eeg_signals
+        .iter()
+        .map(|signal| nanovolts(&signal.physical_dimension))
+        .collect();
+    let eeg_units = match factors[0] {
+        Some(_) => "nV".to_string(),
+        None => eeg_signals[0].physical_dimension.clone(),
+    };
+    let factors: Vec<Float> = factors
+        .into_iter()
+        .map(|factor| factor.unwrap_or(1.0) / scale)
+        .collect();
+
+    let mut eeg = Vec::with_capacity(num_samples * num_channels);
+    let mut acc_values = Vec::with_capacity(num_samples * acc.len());
+    let mut markers = Vec::with_capacity(num_samples);
+    for i in 0..num_samples {
+        let (record, sample) = (i / rate_samples, i % rate_samples);
+        for (signal, factor) in eeg_signals.iter().zip(&factors) {
+            eeg.push(signal.physical(digital(record, signal, sample)) * factor);
+        }
+        for signal in &acc {
+            acc_values.push(signal.physical(digital(record, signal, sample)));
+        }
+        // BioSemi triggers are the low 16 bits of the status channel
+        markers.push(match status {
+            Some(signal) => (digital(record, signal, sample) & 0xFFFF) as Float,
+            None => 0.0,
+        });
+    }
+
+    let first_onset = record_onsets.first().copied().unwrap_or(0.0);
+    let mut triggers: HashMap<u32, String> = HashMap::new();
+    let numeric: HashSet<u32> = events
+        .iter()
+        .filter_map(|(_, description)| description.trim().parse().ok())
+        .collect();
+    let mut next_code = 1;
+    for (onset, description) in &events {
+        let index = sample_index(*onset);
+        if index >= num_samples {
+            continue;
+        }
+
+        // With a status channel, the annotations only describe its codes
+        let code = match description.trim().parse::<u32>() {
+            Ok(code) => code,
+            Err(_) if status.is_some() => {
+                if markers[index] != 0.0 {
+                    triggers.insert(markers[index] as u32, description.clone());
+                }
+                continue;
+            }
+            Err(_) => match triggers.iter().find(|(_, known)| *known == description) {
+                Some((code, _)) => *code,
+                None => {
+                    while numeric.contains(&next_code) || triggers.contains_key(&next_code) {
+                        next_code += 1;
+                    }
+                    triggers.insert(next_code, description.clone());
+                    next_code
+                }
+            },
+        };
+        if status.is_none() {
+            markers[index] = code as Float;
+        }
+    }
+
+    let start_date =
+        header_start + chrono::Duration::milliseconds((first_onset * 1000.0).round() as i64);
+    let timestamps: Vec<DateTime<Utc>> = (0..num_samples)
+        .map(|i| {
+            let seconds =
+                record_onsets[i / rate_samples] + (i % rate_samples) as Float / sampling_rate;
+            header_start + chrono::Duration::milliseconds((seconds * 1000.0).round() as i64)
+        })
+        .collect();
+    let time: Vec<Float> = timestamps
+        .iter()
+        .map(|timestamp| (*timestamp - start_date).num_milliseconds() as Float / 1000.0)
+        .collect();
+
+    let electrodes: Vec<String> = eeg_signals
+        .iter()
+        .map(|signal| {
+            signal
+                .label
+                .strip_prefix("EEG ")
+                .unwrap_or(&signal.label)
+                .to_string()
+        })
+        .collect();
+
+    let mut info = EEGData::new();
+    info.device_info.start_date = Some(start_date);
+    let settings = &mut info.eeg_settings;
+    settings.total_channels = num_channels;
+    settings.eeg_channels = num_channels;
+    settings.records = num_samples;
+    settings.sampling_rate = sampling_rate as f32;
+    settings.eeg_units = eeg_units;
+    settings.montage = electrodes
+        .iter()
+        .enumerate()
+        .map(|(i, electrode)| (i + 1, electrode.clone()))
+        .collect();
+    if let Some(signal) = acc.first() {
+        settings.accelerometer = Some(AccelerometerData {
+            channels: 3,
+            sampling_rate: sampling_rate as f32,
+            units: signal.physical_dimension.clone(),
+        });
+    }
+    info.trigger_info.triggers = triggers;
+
+    let eeg_units = scaled_units(&info.eeg_settings.eeg_units, scale);
+    let parts = EasyParts {
+        electrodes,
+        start_date: Some(start_date),
+        eeg: Array2::from_shape_vec((num_samples, num_channels), eeg)?,
+        acc: if acc.is_empty() {
+            None
+        } else {
+            Some(Array2::from_shape_vec((num_samples, 3), acc_values)?)
+        },
+        markers: Array2::from_shape_vec((num_samples, 1), markers)?,
+        time: Array2::from_shape_vec((num_samples, 1), time)?,
+        timestamps,
+    };
+    let mut recording = Recording::from_parts(parts, info, sampling_rate, &eeg_units)?;
+    recording
+        .log
+        .push(format!("recording read from {:?}: {}", format, Utc::now()));
+    Ok(recording)
+}
+
+/// Parses the start date and time fields of the header, `dd.mm.yy` and `hh.mm.ss`.
+fn parse_start_date(date: &str, time: &str) -> Result<DateTime<Utc>> {
+    let invalid = || EasyError::Header(format!("invalid start date {:?} {:?}", date, time));
+    let numbers = |text: &str| -> Option<Vec<u32>> {
+        let numbers: Vec<u32> = text
+            .split('.')
+            .map(|n| n.trim().parse().ok())
+            .collect::<Option<_>>()?;
+        (numbers.len() == 3).then_some(numbers)
+    };
+    let (date, time) = (
+        numbers(date).ok_or_else(invalid)?,
+        numbers(time).ok_or_else(invalid)?,
+    );
+
+    // Two-digit years from 85 belong to the 20th century
+    let year = if date[2] >= 85 {
+        1900 + date[2]
+    } else {
+        2000 + date[2]
+    };
+    Utc.with_ymd_and_hms(year as i32, date[1], date[0], time[0], time[1], time[2])
+        .single()
+        .ok_or_else(invalid)
+}
+
+/// Splits the annotation bytes of a data record into their onsets and descriptions.
+fn parse_tals(bytes: &[u8]) -> Vec<(Float, Option<Float>, Vec<String>)> {
+    bytes
+        .split(|byte| *byte == 0)
+        .filter(|tal| !tal.is_empty())
+        .filter_map(|tal| {
+            let mut parts = tal.split(|byte| *byte == 0x14);
+            // The onset may be followed by a duration
+            let mut timing = parts.next()?.split(|byte| *byte == 0x15);
+            let seconds = |value: &[u8]| std::str::from_utf8(value).ok()?.trim().parse().ok();
+            let onset: Float = seconds(timing.next()?)?;
+            let duration = timing.next().and_then(seconds);
+            let descriptions = parts
+                .filter(|description| !description.is_empty())
+                .map(|description| String::from_utf8_lossy(description).to_string())
+                .collect();
+            Some((onset, duration, descriptions))
+        })
+        .collect()
+}
+
+/// Returns the number of nanovolts in a unit of voltage, if it is one.
+fn nanovolts(units: &str) -> Option<Float> {
+    match units.trim() {
+        "nV" => Some(1.0),
+        "uV" | "µV" | "μV" => Some(1e3),
+        "mV" => Some(1e6),
+        "V" => Some(1e9),
+        _ => None,
+    }
+}
+
+/// Returns a trimmed ASCII field of a header.
+fn header_field(header: &[u8], offset: usize, width: usize) -> String {
+    String::from_utf8_lossy(&header[offset..offset + width])
+        .trim()
+        .to_string()
+}
+
+/// Appends an ASCII field, truncated or padded with spaces to `width`.
+fn push_field(header: &mut Vec<u8>, value: &str, width: usize) {
+    let value: String = value
+        .chars()
+        .map(|c| {
+            if c.is_ascii() && !c.is_control() {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(width)
+        .collect();
+    header.extend_from_slice(format!("{:<width$}", value, width = width).as_bytes());
+}
+
+/// Formats a number with as many decimals as fit in `width` characters.
+///
+/// Returns `None` if the integer part alone does not fit.
+fn format_header_number(value: Float, width: usize) -> Option<String> {
+    (0..width).rev().find_map(|decimals| {
+        let text = format!("{:.*}", decimals, value);
+        if text.len() > width {
+            return None;
+        }
+        let text = if text.contains('.') {
+            text.trim_end_matches('0').trim_end_matches('.').to_string()
+        } else {
+            text
+        };
+        Some(if text == "-0" { "0".to_string() } else { text })
+    })
+}
+
+/// Parses a number written by [`format_header_number`].
+fn parse_header_number(text: &str) -> Float {
+    text.trim().parse().unwrap_or(0.0)
+}
+
+/// Replaces the non-ASCII micro sign, EDF headers are plain ASCII.
+fn ascii_units(units: &str) -> String {
+    units.replace(['µ', 'μ'], "u")
+}
+
+/// Encodes a time-stamped annotation list with a single annotation.
+///
+/// An empty description gives the time-keeping annotation that starts every data record.
+fn tal(onset: Float, duration: Option<Float>, description: &str) -> Vec<u8> {
+    let seconds = |value: String| {
+        value
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string()
+    };
+    let mut bytes = seconds(format!("{:+.6}", onset)).into_bytes();
+    if let Some(duration) = duration {
+        bytes.push(0x15);
+        bytes.extend(seconds(format!("{:.6}", duration)).bytes());
+    }
+    bytes.push(0x14);
+    bytes.extend(description.replace(['\x14', '\x15', '\0'], " ").bytes());
+    bytes.push(0x14);
+    bytes.push(0);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{SampleRecording, MONTAGE};
+
+    fn sample_recording() -> Recording {
+        SampleRecording::new(500.0, 1250)
+            .eeg(|i, c| ((i * 37 + c * 1000) % 20000) as i64 - 10000)
+            .markers(&[(100, 1), (101, 1), (900, 2)])
+            .start_ms(1609459200250)
+            .settings("EEG units: nV\n")
+            .montage(&MONTAGE)
+            .triggers(&[(1, "Eyes closed")])
+            .recording()
+    }
+
+    fn field(bytes: &[u8], offset: usize, width: usize) -> String {
+        String::from_utf8_lossy(&bytes[offset..offset + width])
+            .trim()
+            .to_string()
+    }
+
+    #[test]
+    fn test_write_edf() {
+        let recording = sample_recording();
+        let mut bytes = Vec::new();
+        write_edf_to(&recording, &mut bytes).unwrap();
+
+        let num_signals = 9;
+        let header_bytes = 256 * (num_signals + 1);
+        assert_eq!(field(&bytes, 0, 8), "0");
+        assert_eq!(field(&bytes, 88, 80), "Startdate 01-JAN-2021 X X X");
+        assert_eq!(field(&bytes, 168, 8), "01.01.21");
+        assert_eq!(field(&bytes, 176, 8), "00.00.00");
+        assert_eq!(field(&bytes, 184, 8), header_bytes.to_string());
+        assert_eq!(field(&bytes, 192, 44), "EDF+C");
+        assert_eq!(field(&bytes, 236, 8), "3");
+        assert_eq!(field(&bytes, 252, 4), "9");
+        assert_eq!(field(&bytes, 256, 16), "EEG Fp1");
+        assert_eq!(field(&bytes, 256 + 8 * 16, 16), "EDF Annotations");
+        assert_eq!(field(&bytes, 256 + num_signals * 96, 8), "uV");
+
+        let samples_offset = 256 + num_signals * 216;
+        assert_eq!(field(&bytes, samples_offset, 8), "500");
+        let annotation_samples: usize = field(&bytes, samples_offset + 8 * 8, 8).parse().unwrap();
+
+        let record_size = (8 * 500 + annotation_samples) * 2;
+        assert_eq!(bytes.len(), header_bytes + 3 * record_size);
+
+        // First sample of the first channel, scaled back with the header range
+        let physical_min = parse_header_number(&field(&bytes, 256 + num_signals * 104, 8));
+        let physical_max = parse_header_number(&field(&bytes, 256 + num_signals * 112, 8));
+        let digital = i16::from_le_bytes([bytes[header_bytes], bytes[header_bytes + 1]]);
+        let physical =
+            physical_min + (digital as Float + 32768.0) / 65535.0 * (physical_max - physical_min);
+        assert!((physical - recording.eeg[[0, 0]]).abs() < 1e-3);
+
+        // Trailing NUL bytes are stripped, padding and TAL terminator alike
+        let annotations = |record: usize| {
+            let start = header_bytes + record * record_size + 8 * 500 * 2;
+            String::from_utf8_lossy(&bytes[start..start + annotation_samples * 2])
+                .trim_end_matches('\0')
+                .to_string()
+        };
+        assert_eq!(
+            annotations(0),
+            "+0.25\u{14}\u{14}\0+0.45\u{14}Eyes closed\u{14}"
+        );
+        assert_eq!(annotations(1), "+1.25\u{14}\u{14}\0+2.05\u{14}2\u{14}");
+        assert_eq!(
+            annotations(2),
+            "+2.25\u{14}\u{14}\0+2.75\u{15}0.5\u{14}Padding\u{14}"
+        );
+
+        // The padding repeats the last sample
+        let padded = |sample: usize| {
+            let offset = header_bytes + 2 * record_size + sample * 2;
+            i16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+        };
+        assert_eq!(padded(249), padded(250));
+        assert_eq!(padded(250), padded(499));
+    }
+
+    #[test]
+    fn test_write_bdf() {
+        let recording = sample_recording();
+        let mut bytes = Vec::new();
+        write_bdf_to(&recording, &mut bytes).unwrap();
+
+        // 8 EEG channels, the status channel and the annotations, no accelerometer in the data
+        let num_signals = 10;
+        let header_bytes = 256 * (num_signals + 1);
+        assert_eq!(&bytes[..8], b"\xffBIOSEMI");
+        assert_eq!(field(&bytes, 192, 44), "BDF+C");
+        assert_eq!(field(&bytes, 252, 4), "10");
+        assert_eq!(field(&bytes, 256 + 8 * 16, 16), "Status");
+        assert_eq!(field(&bytes, 256 + 9 * 16, 16), "BDF Annotations");
+        assert_eq!(field(&bytes, 256 + num_signals * 120, 8), "-8388608");
+        assert_eq!(field(&bytes, 256 + num_signals * 128, 8), "8388607");
+
+        let samples_offset = 256 + num_signals * 216;
+        let annotation_samples: usize = field(&bytes, samples_offset + 9 * 8, 8).parse().unwrap();
+        let record_size = (9 * 500 + annotation_samples) * 3;
+        assert_eq!(bytes.len(), header_bytes + 3 * record_size);
+
+        // 24-bit samples scaled back with the header range
+        let sample = |offset: usize| {
+            let value =
+                i32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], 0]);
+            ((value << 8) >> 8) as Float
+        };
+        let physical_min = parse_header_number(&field(&bytes, 256 + num_signals * 104, 8));
+        let physical_max = parse_header_number(&field(&bytes, 256 + num_signals * 112, 8));
+        let physical = physical_min
+            + (sample(header_bytes) + 8388608.0) / 16777215.0 * (physical_max - physical_min);
+        assert!((physical - recording.eeg[[0, 0]]).abs() < 1e-5);
+
+        // The status channel holds the marker codes unchanged
+        let status_offset = header_bytes + 8 * 500 * 3;
+        assert_eq!(sample(status_offset + 99 * 3), 0.0);
+        assert_eq!(sample(status_offset + 100 * 3), 1.0);
+        assert_eq!(sample(status_offset + 101 * 3), 1.0);
+        assert_eq!(sample(status_offset + record_size + 400 * 3), 2.0);
+    }
+
+    #[test]
+    fn test_read_bdf() {
+        let recording = sample_recording();
+        let mut bytes = Vec::new();
+        write_bdf_to(&recording, &mut bytes).unwrap();
+        let read = read_edf_from(bytes.as_slice(), 1000.0).unwrap();
+
+        // The padding of the last record is left out
+        assert_eq!(read.eeg.dim(), (1250, 8));
+        assert_eq!(read.timestamps.len(), 1250);
+        assert_eq!(read.channels[0].name, "Fp1");
+        assert_eq!(read.channels[0].unit, "uV");
+        assert_eq!(read.sampling_rate, 500.0);
+        assert_eq!(read.to_info(1000.0).eeg_settings.eeg_units, "nV");
+        for (read, written) in read.eeg.iter().zip(recording.eeg.iter()) {
+            assert!((read - written).abs() < 1e-3);
+        }
+
+        // Codes come from the status channel, descriptions from the annotations
+        assert_eq!(read.events.len(), 2);
+        assert_eq!(
+            (read.events[0].sample_index, read.events[0].duration),
+            (100, 2)
+        );
+        assert_eq!(read.events[0].code, 1.0);
+        assert_eq!(read.events[0].description.as_deref(), Some("Eyes closed"));
+        assert_eq!(
+            (read.events[1].sample_index, read.events[1].code),
+            (900, 2.0)
+        );
+        assert_eq!(read.info.trigger_info.triggers.len(), 1);
+
+        assert_eq!(read.start_time.timestamp_millis(), 1609459200250);
+        assert_eq!(read.timestamps[1].timestamp_millis(), 1609459200252);
+        assert_eq!(read.time()[500], 1.0);
+    }
+
+    #[test]
+    fn test_read_edf() {
+        let recording = sample_recording();
+        let mut bytes = Vec::new();
+        write_edf_to(&recording, &mut bytes).unwrap();
+        let read = read_edf_from(bytes.as_slice(), 1.0).unwrap();
+
+        // Values in nanovolts, within the 16-bit quantization step
+        let step = 20.0 * 1000.0 / 65535.0;
+        assert!((read.eeg[[3, 2]] - recording.eeg[[3, 2]] * 1000.0).abs() <= step);
+        assert_eq!(read.eeg_unit(), "nV");
+
+        // Codes from the annotations, a new one for the text description
+        let markers = read.marker_column();
+        assert_eq!(markers[100], 1.0);
+        assert_eq!(markers[101], 0.0);
+        assert_eq!(markers[900], 2.0);
+        assert_eq!(read.info.trigger_info.triggers[&1], "Eyes closed");
+
+        let mut writer = crate::easy_writer::EasyWriter::new(Vec::new(), 1.0, false);
+        writer.write_recording(&read).unwrap();
+        let easy = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert_eq!(easy.lines().count(), 1250);
+        assert!(!read
+            .info
+            .trigger_info
+            .triggers
+            .values()
+            .any(|description| description == PADDING_ANNOTATION));
+        assert!(easy.lines().nth(1).unwrap().ends_with("\t0\t1609459200252"));
+    }
+
+    #[test]
+    fn test_edf_large_range() {
+        // Up to 10 V on the first channel, in microvolts
+        let mut recording = sample_recording();
+        recording.eeg.column_mut(0).mapv_inplace(|x| x * 1e6);
+        let mut bytes = Vec::new();
+        write_edf_to(&recording, &mut bytes).unwrap();
+
+        let num_signals = 9;
+        assert_eq!(field(&bytes, 256 + num_signals * 96, 8), "mV");
+        assert_eq!(field(&bytes, 256 + num_signals * 96 + 8, 8), "uV");
+        assert_eq!(field(&bytes, 256 + num_signals * 104, 8), "-10000");
+
+        // Read back in nanovolts, nothing is clipped
+        let read = read_edf_from(bytes.as_slice(), 1.0).unwrap();
+        let step = 20000.0 * 1e6 / 65535.0;
+        for (read, written) in read.eeg.column(0).iter().zip(recording.eeg.column(0)) {
+            assert!((read - written * 1000.0).abs() <= step);
+        }
+        assert!((read.eeg[[3, 1]] - recording.eeg[[3, 1]] * 1000.0).abs() <= 1.0);
+
+        // Values that are not voltages cannot change prefix
+        recording.channels[0].unit = "counts".to_string();
+        assert!(write_edf_to(&recording, &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_parse_tals() {
+        let tals = parse_tals(b"+0.5\x14\x14\0+1.25\x150.5\x14Blink\x14Artifact\x14\0\0");
+        assert_eq!(tals.len(), 2);
+        assert_eq!(tals[0], (0.5, None, vec![]));
+        assert_eq!(
+            tals[1],
+            (
+                1.25,
+                Some(0.5),
+                vec!["Blink".to_string(), "Artifact".to_string()]
+            )
+        );
+    }
+
+    #[test]
+    fn test_read_padding_event() {
+        // An annotation of another tool named like the padding, away from the end
+        let mut recording = sample_recording();
+        recording.events[0].description = Some(PADDING_ANNOTATION.to_string());
+        let mut bytes = Vec::new();
+        write_edf_to(&recording, &mut bytes).unwrap();
+
+        let read = read_edf_from(bytes.as_slice(), 1.0).unwrap();
+        assert_eq!(read.num_samples(), 1250);
+        assert_eq!(read.events[0].sample_index, 100);
+        assert_eq!(read.events[0].label(), PADDING_ANNOTATION);
+    }
+
+    #[test]
+    fn test_format_header_number() {
+        let format = |value: Float| format_header_number(value, 8);
+        assert_eq!(format(-10000.0).as_deref(), Some("-10000"));
+        assert_eq!(format(1.0 / 3.0).as_deref(), Some("0.333333"));
+        assert_eq!(format(-123456.789).as_deref(), Some("-123457"));
+        assert_eq!(format(-12345678.0), None);
+        assert_eq!(format(1e12), None);
+    }
+}
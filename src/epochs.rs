@@ -0,0 +1,330 @@
+use crate::easy_reader::Float;
+use crate::error::{EasyError, Result};
+use crate::recording::{Channel, Event, Recording};
+use crate::validate::detect_gaps;
+use ndarray::{s, Array1, Array3, Axis};
+
+/// Events epochs are cut around.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventSelection {
+    /// Events with one of these marker codes.
+    Codes(Vec<Float>),
+    /// Events with one of these descriptions in the trigger table.
+    Descriptions(Vec<String>),
+}
+
+impl EventSelection {
+    /// Returns the marker codes selected, resolving descriptions with the trigger table.
+    ///
+    /// A description shared by several codes selects all of them.
+    fn codes(&self, recording: &Recording) -> Result<Vec<Float>> {
+        match self {
+            EventSelection::Codes(codes) => Ok(codes.clone()),
+            EventSelection::Descriptions(descriptions) => {
+                let mut codes = Vec::new();
+                for description in descriptions {
+                    let mut matching: Vec<Float> = recording
+                        .info
+                        .trigger_info
+                        .triggers
+                        .iter()
+                        .filter(|(_, known)| *known == description)
+                        .map(|(code, _)| *code as Float)
+                        .collect();
+                    if matching.is_empty() {
+                        return Err(EasyError::Unsupported(format!(
+                            "no trigger is described as {:?}",
+                            description
+                        )));
+                    }
+                    matching.sort_by(Float::total_cmp);
+                    codes.extend(matching);
+                }
+                Ok(codes)
+            }
+        }
+    }
+}
+
+/// Window of the epochs around their event.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpochOptions {
+    /// Start of the window in seconds relative to the event, negative before it.
+    pub tmin: Float,
+    /// End of the window in seconds relative to the event, included.
+    pub tmax: Float,
+    /// Interval in seconds relative to the event whose mean is subtracted from each channel.
+    pub baseline: Option<(Float, Float)>,
+}
+
+impl EpochOptions {
+    /// Window from `tmin` to `tmax`, without baseline correction.
+    pub fn new(tmin: Float, tmax: Float) -> Self {
+        EpochOptions {
+            tmin,
+            tmax,
+            baseline: None,
+        }
+    }
+
+    /// Sets the baseline interval.
+    pub fn with_baseline(mut self, start: Float, end: Float) -> Self {
+        self.baseline = Some((start, end));
+        self
+    }
+}
+
+/// Why no epoch was cut around an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// The window starts before the first sample or ends after the last one.
+    OutOfBounds,
+    /// The timestamps of the window have a gap, samples were lost.
+    Gap,
+    /// A channel exceeds the peak-to-peak amplitude allowed by
+    /// [`Epochs::reject_peak_to_peak`].
+    PeakToPeak,
+}
+
+/// An event no epoch was kept for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RejectedEpoch {
+    pub event: Event,
+    pub reason: Rejection,
+}
+
+/// Epochs cut from a recording with [`Recording::epochs`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Epochs {
+    /// Samples of each epoch, shape `(num_epochs, num_samples, num_channels)`.
+    pub data: Array3<Float>,
+    /// The event of each epoch, in the order of `data`.
+    pub events: Vec<Event>,
+    /// Time of each sample in seconds relative to the event.
+    pub times: Array1<Float>,
+    /// The channels of the recording.
+    pub channels: Vec<Channel>,
+    pub sampling_rate: Float,
+    /// The selected events left out, with the reason.
+    pub rejected: Vec<RejectedEpoch>,
+}
+
+impl Epochs {
+    /// Number of epochs.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Returns `true` if no epoch was cut.
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Drops the epochs in which a channel spans more than `threshold`, in the unit of the
+    /// channels, between its lowest and highest value.
+    ///
+    /// The dropped epochs are added to [`rejected`](Self::rejected).
+    pub fn reject_peak_to_peak(&mut self, threshold: Float) {
+        let within: Vec<bool> = self
+            .data
+            .outer_iter()
+            .map(|epoch| {
+                epoch.columns().into_iter().all(|column| {
+                    let (min, max) = column
+                        .iter()
+                        .fold((Float::INFINITY, Float::NEG_INFINITY), |(min, max), x| {
+                            (min.min(*x), max.max(*x))
+                        });
+                    max - min <= threshold
+                })
+            })
+            .collect();
+        if within.iter().all(|within| *within) {
+            return;
+        }
+
+        let events = std::mem::take(&mut self.events);
+        for (event, within) in events.into_iter().zip(&within) {
+            if *within {
+                self.events.push(event);
+            } else {
+                self.rejected.push(RejectedEpoch {
+                    event,
+                    reason: Rejection::PeakToPeak,
+                });
+            }
+        }
+        let keep: Vec<usize> = (0..within.len()).filter(|i| within[*i]).collect();
+        self.data = self.data.select(Axis(0), &keep);
+    }
+}
+
+impl Recording {
+    /// Cuts an epoch around each selected event.
+    ///
+    /// The window runs from `tmin` to `tmax` seconds around the first sample of the event, both
+    /// rounded to the nearest sample. Events whose window does not fit in the recording, or
+    /// spans a gap in the timestamps (see [`detect_gaps`]), are listed in
+    /// [`Epochs::rejected`]. With a baseline, the mean of each channel over the baseline
+    /// interval is subtracted from the epoch.
+    pub fn epochs(&self, selection: &EventSelection, options: &EpochOptions) -> Result<Epochs> {
+        let invalid = |message: String| Err(EasyError::Unsupported(message));
+        let rate = self.sampling_rate;
+        let first = (options.tmin * rate).round() as isize;
+        let last = (options.tmax * rate).round() as isize;
+        if first > last {
+            return invalid(format!(
+                "empty epoch window from {} s to {} s",
+                options.tmin, options.tmax
+            ));
+        }
+        let num_samples = (last - first + 1) as usize;
+
+        // Rows of the baseline within the epoch
+        let baseline = match options.baseline {
+            Some((start, end)) => {
+                let start = (start * rate).round() as isize;
+                let end = (end * rate).round() as isize;
+                if start > end || start < first || end > last {
+                    return invalid(format!(
+                        "baseline {:?} outside of the epoch window from {} s to {} s",
+                        options.baseline, options.tmin, options.tmax
+                    ));
+                }
+                Some(((start - first) as usize, (end - first) as usize))
+            }
+            None => None,
+        };
+
+        let codes = selection.codes(self)?;
+        let gaps = detect_gaps(&self.timestamps, rate);
+        let mut events = Vec::new();
+        let mut rejected = Vec::new();
+        for event in self
+            .events
+            .iter()
+            .filter(|event| codes.contains(&event.code))
+        {
+            let start = event.sample_index as isize + first;
+            let end = event.sample_index as isize + last;
+            let reason = if start < 0 || end >= self.num_samples() as isize {
+                Some(Rejection::OutOfBounds)
+            } else if gaps
+                .iter()
+                .any(|gap| gap.sample_index as isize > start && gap.sample_index as isize <= end)
+            {
+                Some(Rejection::Gap)
+            } else {
+                None
+            };
+
+            match reason {
+                Some(reason) => rejected.push(RejectedEpoch {
+                    event: event.clone(),
+                    reason,
+                }),
+                None => events.push(event.clone()),
+            }
+        }
+
+        let mut data = Array3::zeros((events.len(), num_samples, self.num_channels()));
+        for (mut epoch, event) in data.outer_iter_mut().zip(&events) {
+            let start = (event.sample_index as isize + first) as usize;
+            epoch.assign(&self.eeg.slice(s![start..start + num_samples, ..]));
+            if let Some((start, end)) = baseline {
+                if let Some(mean) = epoch.slice(s![start..=end, ..]).mean_axis(Axis(0)) {
+                    epoch -= &mean;
+                }
+            }
+        }
+
+        Ok(Epochs {
+            data,
+            events,
+            times: (first..=last).map(|i| i as Float / rate).collect(),
+            channels: self.channels.clone(),
+            sampling_rate: rate,
+            rejected,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::SampleRecording;
+
+    /// 100 samples at 100 Hz, every channel holding the sample index, with a 30 ms gap
+    /// after sample 70.
+    fn sample_recording() -> Recording {
+        SampleRecording::new(100.0, 100)
+            .eeg(|i, _| i as i64 * 1000)
+            .markers(&[(3, 1), (30, 1), (50, 1), (68, 1), (40, 2), (41, 2), (98, 2)])
+            .gap(70, 30)
+            .triggers(&[(1, "Standard"), (2, "Deviant")])
+            .recording()
+    }
+
+    #[test]
+    fn test_epochs() {
+        let recording = sample_recording();
+        let options = EpochOptions::new(-0.05, 0.1);
+        let epochs = recording
+            .epochs(&EventSelection::Codes(vec![1.0]), &options)
+            .unwrap();
+
+        assert_eq!(epochs.data.dim(), (2, 16, 8));
+        assert_eq!(epochs.times[0], -0.05);
+        assert_eq!(epochs.times[5], 0.0);
+        let starts: Vec<usize> = epochs.events.iter().map(|e| e.sample_index).collect();
+        assert_eq!(starts, vec![30, 50]);
+        assert_eq!(epochs.data[[0, 0, 0]], 25.0);
+        assert_eq!(epochs.data[[1, 15, 7]], 60.0);
+
+        let rejected: Vec<(usize, Rejection)> = epochs
+            .rejected
+            .iter()
+            .map(|r| (r.event.sample_index, r.reason))
+            .collect();
+        assert_eq!(
+            rejected,
+            vec![(3, Rejection::OutOfBounds), (68, Rejection::Gap)]
+        );
+    }
+
+    #[test]
+    fn test_epochs_baseline_and_descriptions() {
+        let recording = sample_recording();
+        let options = EpochOptions::new(-0.02, 0.02).with_baseline(-0.02, 0.0);
+        let epochs = recording
+            .epochs(
+                &EventSelection::Descriptions(vec!["Deviant".to_string()]),
+                &options,
+            )
+            .unwrap();
+
+        // The run of samples 40 and 41 is a single event, 98 runs past the end
+        assert_eq!(epochs.len(), 1);
+        assert_eq!(epochs.events[0].sample_index, 40);
+        assert_eq!(epochs.rejected[0].reason, Rejection::OutOfBounds);
+        let first_channel: Vec<Float> = epochs.data.slice(s![0, .., 0]).to_vec();
+        assert_eq!(first_channel, vec![-1.0, 0.0, 1.0, 2.0, 3.0]);
+
+        // Codes sharing a description are all selected
+        let mut shared = recording.clone();
+        shared
+            .info
+            .trigger_info
+            .triggers
+            .insert(1, "Deviant".to_string());
+        let selection = EventSelection::Descriptions(vec!["Deviant".to_string()]);
+        assert_eq!(selection.codes(&shared).unwrap(), vec![1.0, 2.0]);
+
+        let unknown = EventSelection::Descriptions(vec!["Novel".to_string()]);
+        assert!(recording.epochs(&unknown, &options).is_err());
+        let outside = EpochOptions::new(0.0, 0.1).with_baseline(-0.1, 0.0);
+        assert!(recording
+            .epochs(&EventSelection::Codes(vec![1.0]), &outside)
+            .is_err());
+    }
+}
@@ -0,0 +1,363 @@
+use crate::easy_reader::Float;
+use crate::epochs::{EpochOptions, Epochs, EventSelection};
+use crate::error::{EasyError, Result};
+use crate::recording::{Channel, Recording};
+use ndarray::{Array1, Array2, Axis};
+use serde_json::{json, Value};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// Average response to a condition, or difference between two of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evoked {
+    /// Description of the events averaged, or their code when they have none. Difference
+    /// waves are named `A - B`.
+    pub condition: String,
+    /// Number of epochs averaged.
+    pub num_trials: usize,
+    /// Mean over the epochs, shape `(num_samples, num_channels)`.
+    pub mean: Array2<Float>,
+    /// Standard error of the mean, shape `(num_samples, num_channels)`.
+    pub sem: Array2<Float>,
+    /// Time of each sample in seconds relative to the events.
+    pub times: Array1<Float>,
+    pub channels: Vec<Channel>,
+}
+
+impl Evoked {
+    /// Returns the difference wave `self - other`.
+    ///
+    /// The standard error is that of the difference of two independent means, and the number
+    /// of trials counts the epochs of both conditions.
+    pub fn difference(&self, other: &Evoked) -> Result<Evoked> {
+        if self.mean.dim() != other.mean.dim() || self.times != other.times {
+            return Err(EasyError::Unsupported(format!(
+                "cannot subtract {} from {}, the epochs differ",
+                other.condition, self.condition
+            )));
+        }
+        Ok(Evoked {
+            condition: format!("{} - {}", self.condition, other.condition),
+            num_trials: self.num_trials + other.num_trials,
+            mean: &self.mean - &other.mean,
+            sem: (&self.sem * &self.sem + &other.sem * &other.sem).mapv(Float::sqrt),
+            times: self.times.clone(),
+            channels: self.channels.clone(),
+        })
+    }
+}
+
+impl Epochs {
+    /// Averages all the epochs as a single condition named `condition`.
+    ///
+    /// The standard error uses the sample standard deviation, and is `0` with a single epoch.
+    /// Without epochs, e.g. when all were rejected, there is nothing to average and an error is
+    /// returned.
+    pub fn average(&self, condition: &str) -> Result<Evoked> {
+        let num_trials = self.len();
+        let Some(mean) = self.data.mean_axis(Axis(0)).filter(|_| num_trials > 0) else {
+            return Err(EasyError::Unsupported(format!(
+                "no epochs of {} left to average",
+                condition
+            )));
+        };
+        let sem = if num_trials > 1 {
+            self.data.std_axis(Axis(0), 1.0) / (num_trials as Float).sqrt()
+        } else {
+            Array2::zeros(mean.dim())
+        };
+
+        Ok(Evoked {
+            condition: condition.to_string(),
+            num_trials,
+            mean,
+            sem,
+            times: self.times.clone(),
+            channels: self.channels.clone(),
+        })
+    }
+
+    /// Averages the epochs of each marker code, in the order the codes first appear.
+    pub fn evoked(&self) -> Result<Vec<Evoked>> {
+        let mut codes: Vec<Float> = Vec::new();
+        for event in &self.events {
+            if !codes.contains(&event.code) {
+                codes.push(event.code);
+            }
+        }
+
+        codes
+            .into_iter()
+            .map(|code| {
+                let indices: Vec<usize> = (0..self.len())
+                    .filter(|i| self.events[*i].code == code)
+                    .collect();
+                let condition = Epochs {
+                    data: self.data.select(Axis(0), &indices),
+                    events: indices.iter().map(|i| self.events[*i].clone()).collect(),
+                    times: self.times.clone(),
+                    channels: self.channels.clone(),
+                    sampling_rate: self.sampling_rate,
+                    rejected: Vec::new(),
+                };
+                condition.average(&self.events[indices[0]].label())
+            })
+            .collect()
+    }
+}
+
+impl Recording {
+    /// Epochs the recording around the selected events and averages each marker code.
+    ///
+    /// With `peak_to_peak`, epochs in which a channel spans more than this amplitude are left
+    /// out, see [`Epochs::reject_peak_to_peak`].
+    pub fn evoked(
+        &self,
+        selection: &EventSelection,
+        options: &EpochOptions,
+        peak_to_peak: Option<Float>,
+    ) -> Result<Vec<Evoked>> {
+        let mut epochs = self.epochs(selection, options)?;
+        if let Some(threshold) = peak_to_peak {
+            epochs.reject_peak_to_peak(threshold);
+        }
+        epochs.evoked()
+    }
+
+    /// Returns the difference wave between the averages of two conditions, `a - b`.
+    ///
+    /// A condition left without epochs, e.g. when all of them were rejected, is an error rather
+    /// than an average of zero.
+    pub fn contrast(
+        &self,
+        a: &EventSelection,
+        b: &EventSelection,
+        options: &EpochOptions,
+        peak_to_peak: Option<Float>,
+    ) -> Result<Evoked> {
+        let average = |selection: &EventSelection| -> Result<Evoked> {
+            let mut epochs = self.epochs(selection, options)?;
+            if let Some(threshold) = peak_to_peak {
+                epochs.reject_peak_to_peak(threshold);
+            }
+            let condition = match epochs.events.first() {
+                Some(event) if epochs.events.iter().all(|e| e.code == event.code) => event.label(),
+                _ => selection_label(selection),
+            };
+            epochs.average(&condition)
+        };
+        average(a)?.difference(&average(b)?)
+    }
+}
+
+/// Names a selection of several codes or descriptions.
+fn selection_label(selection: &EventSelection) -> String {
+    match selection {
+        EventSelection::Codes(codes) => codes
+            .iter()
+            .map(|code| code.to_string())
+            .collect::<Vec<_>>()
+            .join("+"),
+        EventSelection::Descriptions(descriptions) => descriptions.join("+"),
+    }
+}
+
+/// Writes evoked responses as a CSV file.
+///
+/// See [`write_csv_to`].
+pub fn write_csv(evoked: &[Evoked], filename: &str) -> Result<()> {
+    let mut file = BufWriter::new(File::create(filename)?);
+    write_csv_to(evoked, &mut file)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Writes evoked responses in the CSV format to any writer.
+///
+/// The table is in long format, one row per condition, channel and sample, with the columns
+/// `condition`, `num_trials`, `channel`, `unit`, `time`, `mean` and `sem`.
+pub fn write_csv_to<W: Write>(evoked: &[Evoked], writer: &mut W) -> Result<()> {
+    let mut csv = csv::Writer::from_writer(writer);
+    let to_error = |e| EasyError::from_csv(e, false);
+    csv.write_record([
+        "condition",
+        "num_trials",
+        "channel",
+        "unit",
+        "time",
+        "mean",
+        "sem",
+    ])
+    .map_err(to_error)?;
+
+    for response in evoked {
+        let num_trials = response.num_trials.to_string();
+        for (c, channel) in response.channels.iter().enumerate() {
+            for (i, time) in response.times.iter().enumerate() {
+                csv.write_record([
+                    response.condition.as_str(),
+                    num_trials.as_str(),
+                    channel.name.as_str(),
+                    channel.unit.as_str(),
+                    &time.to_string(),
+                    &response.mean[[i, c]].to_string(),
+                    &response.sem[[i, c]].to_string(),
+                ])
+                .map_err(to_error)?;
+            }
+        }
+    }
+    csv.flush()?;
+    Ok(())
+}
+
+/// Writes evoked responses as a JSON file.
+///
+/// See [`write_json_to`].
+pub fn write_json(evoked: &[Evoked], filename: &str) -> Result<()> {
+    let mut file = BufWriter::new(File::create(filename)?);
+    write_json_to(evoked, &mut file)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Writes evoked responses in the JSON format to any writer.
+///
+/// The document is an array with one object per response, holding its `condition`,
+/// `num_trials`, `times`, `channels` (name and unit), and `mean` and `sem` as one array of
+/// samples per channel.
+pub fn write_json_to<W: Write>(evoked: &[Evoked], writer: &mut W) -> Result<()> {
+    let per_channel = |values: &Array2<Float>| -> Value {
+        values
+            .columns()
+            .into_iter()
+            .map(|column| column.to_vec())
+            .collect::<Vec<_>>()
+            .into()
+    };
+    let document: Vec<Value> = evoked
+        .iter()
+        .map(|response| {
+            json!({
+                "condition": response.condition,
+                "num_trials": response.num_trials,
+                "times": response.times.to_vec(),
+                "channels": response
+                    .channels
+                    .iter()
+                    .map(|channel| json!({ "name": channel.name, "unit": channel.unit }))
+                    .collect::<Vec<_>>(),
+                "mean": per_channel(&response.mean),
+                "sem": per_channel(&response.sem),
+            })
+        })
+        .collect();
+
+    serde_json::to_writer_pretty(&mut *writer, &document).map_err(std::io::Error::from)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::SampleRecording;
+
+    /// 200 samples at 100 Hz. Standards (1) evoke a bump of 10 uV, deviants (2) one of 20 uV
+    /// plus a trial-dependent offset; the last deviant has an artifact.
+    fn sample_recording() -> Recording {
+        let onsets = [(20, 1), (50, 2), (80, 1), (110, 2), (140, 1), (170, 2)];
+        let mut eeg = vec![0i64; 200];
+        for (trial, (onset, code)) in onsets.iter().enumerate() {
+            for i in 0..5 {
+                eeg[onset + i] = match code {
+                    1 => 10_000,
+                    _ => 20_000 + trial as i64 * 1000,
+                };
+            }
+        }
+        eeg[172] = 500_000;
+
+        SampleRecording::new(100.0, 200)
+            .eeg(move |i, _| eeg[i])
+            .markers(&onsets)
+            .triggers(&[(1, "Standard"), (2, "Deviant")])
+            .recording()
+    }
+
+    #[test]
+    fn test_evoked() {
+        let recording = sample_recording();
+        let options = EpochOptions::new(-0.05, 0.1).with_baseline(-0.05, -0.01);
+        let selection = EventSelection::Codes(vec![1.0, 2.0]);
+
+        let evoked = recording.evoked(&selection, &options, None).unwrap();
+        assert_eq!(evoked.len(), 2);
+        assert_eq!(evoked[0].condition, "Standard");
+        assert_eq!(evoked[0].num_trials, 3);
+        assert_eq!(evoked[0].mean.dim(), (16, 8));
+        assert_eq!(evoked[0].mean[[5, 0]], 10.0);
+        assert_eq!(evoked[0].sem[[5, 0]], 0.0);
+        // Deviants of trials 1, 3 and 5 peak at 21, 23 and 25 uV
+        assert_eq!(evoked[1].mean[[5, 0]], 23.0);
+        assert!((evoked[1].sem[[5, 0]] - 2.0 / 3.0_f64.sqrt()).abs() < 1e-12);
+
+        let evoked = recording.evoked(&selection, &options, Some(100.0)).unwrap();
+        assert_eq!(evoked[1].num_trials, 2);
+        assert_eq!(evoked[1].mean[[5, 0]], 22.0);
+
+        let contrast = recording
+            .contrast(
+                &EventSelection::Descriptions(vec!["Deviant".to_string()]),
+                &EventSelection::Descriptions(vec!["Standard".to_string()]),
+                &options,
+                Some(100.0),
+            )
+            .unwrap();
+        assert_eq!(contrast.condition, "Deviant - Standard");
+        assert_eq!(contrast.num_trials, 5);
+        assert_eq!(contrast.mean[[5, 3]], 12.0);
+        assert_eq!(contrast.mean[[0, 3]], 0.0);
+
+        // Every deviant spans more than 15 uV, none is left to contrast
+        assert!(recording
+            .contrast(
+                &EventSelection::Descriptions(vec!["Deviant".to_string()]),
+                &EventSelection::Descriptions(vec!["Standard".to_string()]),
+                &options,
+                Some(15.0),
+            )
+            .is_err());
+        let mut empty = recording
+            .epochs(&EventSelection::Codes(vec![2.0]), &options)
+            .unwrap();
+        empty.reject_peak_to_peak(15.0);
+        assert!(empty.is_empty());
+        assert!(empty.average("Deviant").is_err());
+    }
+
+    #[test]
+    fn test_export() {
+        let recording = sample_recording();
+        let options = EpochOptions::new(0.0, 0.02);
+        let evoked = recording
+            .evoked(&EventSelection::Codes(vec![1.0, 2.0]), &options, None)
+            .unwrap();
+
+        let mut csv = Vec::new();
+        write_csv_to(&evoked, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 1 + 2 * 8 * 3);
+        assert_eq!(lines[0], "condition,num_trials,channel,unit,time,mean,sem");
+        assert_eq!(lines[2], "Standard,3,Ch1,uV,0.01,10,0");
+
+        let mut json = Vec::new();
+        write_json_to(&evoked, &mut json).unwrap();
+        let json: Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json[1]["condition"], "Deviant");
+        assert_eq!(json[1]["channels"][7]["name"], "Ch8");
+        assert_eq!(json[1]["times"], json!([0.0, 0.01, 0.02]));
+        assert_eq!(json[1]["mean"][0][0], 23.0);
+    }
+}
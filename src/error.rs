@@ -0,0 +1,90 @@
+use std::io;
+use thiserror::Error;
+
+/// Errors returned while reading and writing `.easy` and `.info` files.
+#[derive(Debug, Error)]
+pub enum EasyError {
+    /// The file could not be opened or read.
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    /// The file name does not end with `.easy` or `.easy.gz`.
+    #[error("unsupported file extension for {0}, expected .easy or .easy.gz")]
+    BadExtension(String),
+
+    /// A row does not have the number of columns expected for a NIC device.
+    #[error("unexpected number of columns on line {line}: found {found}")]
+    ColumnCount { line: u64, found: usize },
+
+    /// A value could not be parsed as a number.
+    ///
+    /// `line` and `column` are 1-based.
+    #[error("cannot parse {value:?} on line {line}, column {column}")]
+    Parse {
+        line: u64,
+        column: usize,
+        value: String,
+    },
+
+    /// The `.easy` file holds no samples.
+    #[error("the .easy file is empty")]
+    EmptyFile,
+
+    /// The gzip stream of a `.easy.gz` file is corrupted or truncated.
+    #[error("corrupted gzip stream: {0}")]
+    Gzip(io::Error),
+
+    /// The `.info` file does not describe the `.easy` file it is paired with.
+    #[error("the .info file does not match the data: {0}")]
+    InfoMismatch(String),
+
+    /// The header of a binary recording is malformed or describes an unsupported layout.
+    #[error("invalid header: {0}")]
+    Header(String),
+
+    /// The data cannot be represented in the target file format.
+    #[error("unsupported data: {0}")]
+    Unsupported(String),
+
+    /// The parsed values could not be arranged into an array.
+    #[error("array shape error: {0}")]
+    Shape(#[from] ndarray::ShapeError),
+
+    /// Any other error reported by the CSV parser, e.g. invalid UTF-8.
+    #[error("CSV error: {0}")]
+    Csv(csv::Error),
+
+    /// The data could not be converted into Arrow arrays.
+    #[cfg(feature = "arrow")]
+    #[error("Arrow error: {0}")]
+    Arrow(#[from] arrow_schema::ArrowError),
+
+    /// The Parquet file could not be written.
+    #[cfg(feature = "arrow")]
+    #[error("Parquet error: {0}")]
+    Parquet(#[from] parquet::errors::ParquetError),
+}
+
+impl EasyError {
+    /// Converts a CSV error, attributing I/O failures to the gzip layer when the source is compressed.
+    pub(crate) fn from_csv(error: csv::Error, gzip: bool) -> Self {
+        let line = error.position().map_or(0, |position| position.line());
+        if let csv::ErrorKind::UnequalLengths { len, .. } = error.kind() {
+            return EasyError::ColumnCount {
+                line,
+                found: *len as usize,
+            };
+        }
+        if !error.is_io_error() {
+            return EasyError::Csv(error);
+        }
+
+        match error.into_kind() {
+            csv::ErrorKind::Io(error) if gzip => EasyError::Gzip(error),
+            csv::ErrorKind::Io(error) => EasyError::Io(error),
+            kind => EasyError::Io(io::Error::other(format!("{kind:?}"))),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, EasyError>;
@@ -0,0 +1,574 @@
+use crate::easy_reader::{Chunk, Float};
+use crate::error::{EasyError, Result};
+use crate::recording::Recording;
+use chrono::Utc;
+use ndarray::{ArrayViewMut1, ArrayViewMut2};
+use std::f64::consts::PI;
+
+/// Frequency response of a filter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterKind {
+    /// Removes the frequencies below the cutoff, in Hz.
+    HighPass(Float),
+    /// Removes the frequencies above the cutoff, in Hz.
+    LowPass(Float),
+    /// Keeps the frequencies between the two cutoffs, in Hz.
+    BandPass(Float, Float),
+    /// Removes the frequencies between the two cutoffs, in Hz.
+    BandStop(Float, Float),
+}
+
+/// A second-order section, normalized so that `a0` is `1`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Section {
+    b: [Float; 3],
+    a: [Float; 2],
+}
+
+impl Section {
+    /// Processes a sample, updating the state of the transposed direct form II.
+    fn step(&self, x: Float, state: &mut [Float; 2]) -> Float {
+        let y = self.b[0] * x + state[0];
+        state[0] = self.b[1] * x - self.a[0] * y + state[1];
+        state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+
+    /// DC gain of the section.
+    fn dc_gain(&self) -> Float {
+        (self.b[0] + self.b[1] + self.b[2]) / (1.0 + self.a[0] + self.a[1])
+    }
+
+    /// State the section settles in when its input stays at `input`.
+    fn steady_state(&self, input: Float) -> [Float; 2] {
+        let gain = self.dc_gain();
+        let z2 = input * (self.b[2] - self.a[1] * gain);
+        [input * (self.b[1] - self.a[0] * gain) + z2, z2]
+    }
+
+    /// Complex gain at the normalized angular frequency `w`, in radians per sample.
+    fn response(&self, w: Float) -> Complex {
+        let z1 = Complex::polar(-w);
+        let z2 = Complex::polar(-2.0 * w);
+        let numerator = Complex::real(self.b[0])
+            .add(z1.scale(self.b[1]))
+            .add(z2.scale(self.b[2]));
+        let denominator = Complex::real(1.0)
+            .add(z1.scale(self.a[0]))
+            .add(z2.scale(self.a[1]));
+        numerator.div(denominator)
+    }
+}
+
+/// A Butterworth filter, as a cascade of second-order sections.
+///
+/// The filter is designed from an analog prototype mapped to the sampling rate with the
+/// bilinear transform, so the cutoffs are exact at the sampling rate of the recording.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    kind: FilterKind,
+    order: usize,
+    sampling_rate: Float,
+    sections: Vec<Section>,
+}
+
+impl Filter {
+    /// Designs a Butterworth filter of the given order.
+    ///
+    /// Band-pass and band-stop filters have twice as many poles as their order. The cutoffs
+    /// must lie strictly between `0` and the Nyquist frequency.
+    pub fn butterworth(kind: FilterKind, order: usize, sampling_rate: Float) -> Result<Self> {
+        let nyquist = sampling_rate / 2.0;
+        let cutoffs = match kind {
+            FilterKind::HighPass(cutoff) | FilterKind::LowPass(cutoff) => vec![cutoff],
+            FilterKind::BandPass(low, high) | FilterKind::BandStop(low, high) => {
+                if low >= high {
+                    return Err(EasyError::Unsupported(format!(
+                        "the band from {} Hz to {} Hz is empty",
+                        low, high
+                    )));
+                }
+                vec![low, high]
+            }
+        };
+        if order == 0 {
+            return Err(EasyError::Unsupported(
+                "the order of a filter must be at least 1".to_string(),
+            ));
+        }
+        if let Some(cutoff) = cutoffs
+            .iter()
+            .find(|cutoff| !(**cutoff > 0.0 && **cutoff < nyquist))
+        {
+            return Err(EasyError::Unsupported(format!(
+                "cutoff of {} Hz outside of (0, {}) Hz",
+                cutoff, nyquist
+            )));
+        }
+
+        let fs2 = 2.0 * sampling_rate;
+        let prewarp = |frequency: Float| fs2 * (PI * frequency / sampling_rate).tan();
+        let prototype = (0..order)
+            .map(|k| Complex::polar(PI * (2 * k + 1 + order) as Float / (2 * order) as Float));
+
+        // Analog poles, the zeros are handled per kind below
+        let analog: Vec<Complex> = match kind {
+            FilterKind::LowPass(cutoff) => prototype.map(|p| p.scale(prewarp(cutoff))).collect(),
+            FilterKind::HighPass(cutoff) => prototype
+                .map(|p| Complex::real(prewarp(cutoff)).div(p))
+                .collect(),
+            FilterKind::BandPass(low, high) | FilterKind::BandStop(low, high) => {
+                let (low, high) = (prewarp(low), prewarp(high));
+                let center = Complex::real(low * high);
+                let half_width = (high - low) / 2.0;
+                prototype
+                    .flat_map(|p| {
+                        let q = match kind {
+                            FilterKind::BandPass(..) => p.scale(half_width),
+                            _ => Complex::real(half_width).div(p),
+                        };
+                        let root = q.mul(q).sub(center).sqrt();
+                        [q.add(root), q.sub(root)]
+                    })
+                    .collect()
+            }
+        };
+        let digital = analog
+            .into_iter()
+            .map(|s| Complex::real(fs2).add(s).div(Complex::real(fs2).sub(s)));
+
+        // Conjugate pairs become sections, real poles are paired with each other
+        let mut denominators = Vec::new();
+        let mut real_poles = Vec::new();
+        for pole in digital {
+            if pole.im > 1e-12 {
+                denominators.push([-2.0 * pole.re, pole.re * pole.re + pole.im * pole.im]);
+            } else if pole.im.abs() <= 1e-12 {
+                real_poles.push(pole.re);
+            }
+        }
+        for pair in real_poles.chunks(2) {
+            denominators.push(match pair {
+                [a, b] => [-(a + b), a * b],
+                [a] => [-a, 0.0],
+                _ => unreachable!(),
+            });
+        }
+
+        let center =
+            |low: Float, high: Float| 2.0 * ((prewarp(low) * prewarp(high)).sqrt() / fs2).atan();
+        let (numerator, reference) = match kind {
+            FilterKind::LowPass(_) => ([1.0, 2.0, 1.0], 0.0),
+            FilterKind::HighPass(_) => ([1.0, -2.0, 1.0], PI),
+            FilterKind::BandPass(low, high) => ([1.0, 0.0, -1.0], center(low, high)),
+            FilterKind::BandStop(low, high) => ([1.0, -2.0 * center(low, high).cos(), 1.0], 0.0),
+        };
+        let mut sections: Vec<Section> = denominators
+            .into_iter()
+            .map(|a| Section {
+                // First-order sections have a single zero
+                b: if a[1] == 0.0 {
+                    [1.0, numerator[1] / 2.0, 0.0]
+                } else {
+                    numerator
+                },
+                a,
+            })
+            .collect();
+
+        // Unity gain in the pass band
+        let gain = sections
+            .iter()
+            .fold(Complex::real(1.0), |gain, section| {
+                gain.mul(section.response(reference))
+            })
+            .abs();
+        for b in sections[0].b.iter_mut() {
+            *b /= gain;
+        }
+
+        Ok(Filter {
+            kind,
+            order,
+            sampling_rate,
+            sections,
+        })
+    }
+
+    /// Designs a 4th-order Butterworth high-pass filter.
+    pub fn highpass(cutoff: Float, sampling_rate: Float) -> Result<Self> {
+        Self::butterworth(FilterKind::HighPass(cutoff), 4, sampling_rate)
+    }
+
+    /// Designs a 4th-order Butterworth low-pass filter.
+    pub fn lowpass(cutoff: Float, sampling_rate: Float) -> Result<Self> {
+        Self::butterworth(FilterKind::LowPass(cutoff), 4, sampling_rate)
+    }
+
+    /// Designs a 4th-order Butterworth band-pass filter.
+    pub fn bandpass(low: Float, high: Float, sampling_rate: Float) -> Result<Self> {
+        Self::butterworth(FilterKind::BandPass(low, high), 4, sampling_rate)
+    }
+
+    /// Designs a notch removing `bandwidth` Hz around `frequency`, a 2nd-order Butterworth
+    /// band-stop filter.
+    ///
+    /// The edges of the band are shifted slightly so that the gain is exactly zero at
+    /// `frequency`.
+    pub fn notch(frequency: Float, bandwidth: Float, sampling_rate: Float) -> Result<Self> {
+        let half = bandwidth / 2.0;
+        if !(half > 0.0 && frequency - half > 0.0 && frequency + half < sampling_rate / 2.0) {
+            return Err(EasyError::Unsupported(format!(
+                "notch of {} Hz around {} Hz outside of (0, {}) Hz",
+                bandwidth,
+                frequency,
+                sampling_rate / 2.0
+            )));
+        }
+
+        // Prewarped edges around the prewarped center, which the band-stop is centered on
+        let prewarp = |frequency: Float| (PI * frequency / sampling_rate).tan();
+        let unwarp = |warped: Float| warped.atan() * sampling_rate / PI;
+        let center = prewarp(frequency);
+        let width = prewarp(frequency + half) - prewarp(frequency - half);
+        let low = ((width * width + 4.0 * center * center).sqrt() - width) / 2.0;
+        Self::butterworth(
+            FilterKind::BandStop(unwarp(low), unwarp(low + width)),
+            1,
+            sampling_rate,
+        )
+    }
+
+    pub fn kind(&self) -> FilterKind {
+        self.kind
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    /// Sampling rate the filter was designed for, in Hz.
+    pub fn sampling_rate(&self) -> Float {
+        self.sampling_rate
+    }
+
+    /// Returns the gain of the filter at `frequency`, in Hz, applied once.
+    ///
+    /// Applied forward and backward, the gain is squared.
+    pub fn gain(&self, frequency: Float) -> Float {
+        let w = 2.0 * PI * frequency / self.sampling_rate;
+        self.sections
+            .iter()
+            .fold(Complex::real(1.0), |gain, section| {
+                gain.mul(section.response(w))
+            })
+            .abs()
+    }
+
+    /// Filters a signal in place forward, then backward, leaving no phase shift.
+    ///
+    /// The signal is extended at both ends by its reflection around its first and last values,
+    /// and each pass starts from the steady state of the filter, which reduces edge effects.
+    pub fn filtfilt(&self, mut signal: ArrayViewMut1<Float>) {
+        let length = signal.len();
+        if length < 2 {
+            return;
+        }
+        let padding = (3 * (2 * self.sections.len() + 1)).min(length - 1);
+
+        let (first, last) = (signal[0], signal[length - 1]);
+        let mut extended = Vec::with_capacity(length + 2 * padding);
+        extended.extend((1..=padding).rev().map(|i| 2.0 * first - signal[i]));
+        extended.extend(signal.iter());
+        extended.extend((1..=padding).map(|i| 2.0 * last - signal[length - 1 - i]));
+
+        self.filter_forward(&mut extended);
+        extended.reverse();
+        self.filter_forward(&mut extended);
+        extended.reverse();
+
+        for (value, filtered) in signal.iter_mut().zip(&extended[padding..]) {
+            *value = *filtered;
+        }
+    }
+
+    /// Filters each column of `eeg` in place with [`filtfilt`](Self::filtfilt).
+    pub fn filtfilt_columns(&self, mut eeg: ArrayViewMut2<Float>) {
+        for column in eeg.columns_mut() {
+            self.filtfilt(column);
+        }
+    }
+
+    /// Returns a causal filter keeping its state across the chunks of a stream.
+    pub fn streaming(&self) -> StreamingFilter {
+        StreamingFilter {
+            filter: self.clone(),
+            states: Vec::new(),
+        }
+    }
+
+    /// Filters a signal forward, starting from the steady state for its first value.
+    fn filter_forward(&self, signal: &mut [Float]) {
+        let Some(first) = signal.first().copied() else {
+            return;
+        };
+        let mut states = self.steady_states(first);
+        for value in signal.iter_mut() {
+            for (section, state) in self.sections.iter().zip(states.iter_mut()) {
+                *value = section.step(*value, state);
+            }
+        }
+    }
+
+    /// Returns the state of each section when the input stays at `input`.
+    fn steady_states(&self, input: Float) -> Vec<[Float; 2]> {
+        let mut level = input;
+        self.sections
+            .iter()
+            .map(|section| {
+                let state = section.steady_state(level);
+                level *= section.dc_gain();
+                state
+            })
+            .collect()
+    }
+}
+
+/// A filter applied causally to consecutive blocks of samples, e.g. the chunks of
+/// [`EasyReader::stream`](crate::easy_reader::EasyReader::stream).
+///
+/// Unlike [`Filter::filtfilt`], a single forward pass delays the signal by the phase response
+/// of the filter, but the result does not depend on how the samples are split into chunks.
+#[derive(Debug, Clone)]
+pub struct StreamingFilter {
+    filter: Filter,
+    /// State of each section of each channel, set from the first sample.
+    states: Vec<Vec<[Float; 2]>>,
+}
+
+impl StreamingFilter {
+    /// Filters a block of samples, shape `(num_samples, num_channels)`, in place.
+    ///
+    /// Every block must have the channels of the first one.
+    pub fn process(&mut self, mut eeg: ArrayViewMut2<Float>) -> Result<()> {
+        if eeg.nrows() == 0 {
+            return Ok(());
+        }
+        if self.states.is_empty() {
+            self.states = eeg
+                .row(0)
+                .iter()
+                .map(|value| self.filter.steady_states(*value))
+                .collect();
+        } else if self.states.len() != eeg.ncols() {
+            return Err(EasyError::Unsupported(format!(
+                "block of {} channels after blocks of {}",
+                eeg.ncols(),
+                self.states.len()
+            )));
+        }
+
+        for mut row in eeg.rows_mut() {
+            for (value, states) in row.iter_mut().zip(self.states.iter_mut()) {
+                for (section, state) in self.filter.sections.iter().zip(states.iter_mut()) {
+                    *value = section.step(*value, state);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Filters the EEG of a chunk in place.
+    pub fn process_chunk(&mut self, chunk: &mut Chunk) -> Result<()> {
+        self.process(chunk.eeg.view_mut())
+    }
+
+    /// Forgets the state, the next block starts a new signal.
+    pub fn reset(&mut self) {
+        self.states.clear();
+    }
+}
+
+impl Recording {
+    /// Filters every EEG channel in place with no phase shift, see [`Filter::filtfilt`].
+    ///
+    /// The filter must have been designed for the sampling rate of the recording.
+    pub fn filter(&mut self, filter: &Filter) -> Result<()> {
+        if filter.sampling_rate != self.sampling_rate {
+            return Err(EasyError::Unsupported(format!(
+                "filter designed for {} Hz applied to a recording at {} Hz",
+                filter.sampling_rate, self.sampling_rate
+            )));
+        }
+        filter.filtfilt_columns(self.eeg.view_mut());
+        self.log.push(format!(
+            "zero-phase Butterworth filter of order {} applied, {:?}: {}",
+            filter.order,
+            filter.kind,
+            Utc::now()
+        ));
+        Ok(())
+    }
+}
+
+/// The complex arithmetic needed to place poles.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Complex {
+    re: Float,
+    im: Float,
+}
+
+impl Complex {
+    fn real(re: Float) -> Self {
+        Complex { re, im: 0.0 }
+    }
+
+    /// Point of the unit circle at angle `theta`.
+    fn polar(theta: Float) -> Self {
+        Complex {
+            re: theta.cos(),
+            im: theta.sin(),
+        }
+    }
+
+    fn add(self, other: Complex) -> Self {
+        Complex {
+            re: self.re + other.re,
+            im: self.im + other.im,
+        }
+    }
+
+    fn sub(self, other: Complex) -> Self {
+        Complex {
+            re: self.re - other.re,
+            im: self.im - other.im,
+        }
+    }
+
+    fn mul(self, other: Complex) -> Self {
+        Complex {
+            re: self.re * other.re - self.im * other.im,
+            im: self.re * other.im + self.im * other.re,
+        }
+    }
+
+    fn div(self, other: Complex) -> Self {
+        let norm = other.re * other.re + other.im * other.im;
+        Complex {
+            re: (self.re * other.re + self.im * other.im) / norm,
+            im: (self.im * other.re - self.re * other.im) / norm,
+        }
+    }
+
+    fn scale(self, factor: Float) -> Self {
+        Complex {
+            re: self.re * factor,
+            im: self.im * factor,
+        }
+    }
+
+    fn abs(self) -> Float {
+        self.re.hypot(self.im)
+    }
+
+    /// Principal square root.
+    fn sqrt(self) -> Self {
+        let modulus = self.abs();
+        let re = ((modulus + self.re) / 2.0).sqrt();
+        let im = ((modulus - self.re) / 2.0).sqrt();
+        Complex {
+            re,
+            im: if self.im < 0.0 { -im } else { im },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{s, Array1, Array2};
+
+    fn sine(frequency: Float, sampling_rate: Float, num_samples: usize) -> Array1<Float> {
+        Array1::from_shape_fn(num_samples, |i| {
+            (2.0 * PI * frequency * i as Float / sampling_rate).sin()
+        })
+    }
+
+    /// Root mean square away from the edges.
+    fn rms(signal: &Array1<Float>) -> Float {
+        let middle = signal.slice(s![200..signal.len() - 200]);
+        (middle.mapv(|x| x * x).sum() / middle.len() as Float).sqrt()
+    }
+
+    #[test]
+    fn test_butterworth_response() {
+        let rate = 500.0;
+        let lowpass = Filter::lowpass(40.0, rate).unwrap();
+        assert!((lowpass.gain(0.0) - 1.0).abs() < 1e-12);
+        assert!((lowpass.gain(40.0) - 0.5_f64.sqrt()).abs() < 1e-9);
+        assert!(lowpass.gain(120.0) < 0.01);
+
+        let highpass = Filter::butterworth(FilterKind::HighPass(1.0), 3, rate).unwrap();
+        assert_eq!(highpass.sections.len(), 2);
+        assert!(highpass.gain(0.0) < 1e-12);
+        assert!((highpass.gain(1.0) - 0.5_f64.sqrt()).abs() < 1e-9);
+        assert!((highpass.gain(100.0) - 1.0).abs() < 1e-6);
+
+        let bandpass = Filter::bandpass(8.0, 12.0, rate).unwrap();
+        assert_eq!(bandpass.sections.len(), 4);
+        assert!((bandpass.gain(8.0) - 0.5_f64.sqrt()).abs() < 1e-9);
+        assert!((bandpass.gain(12.0) - 0.5_f64.sqrt()).abs() < 1e-9);
+        assert!(bandpass.gain(50.0) < 1e-3);
+
+        let notch = Filter::notch(50.0, 2.0, rate).unwrap();
+        assert!(notch.gain(50.0) < 1e-9);
+        assert!((notch.gain(10.0) - 1.0).abs() < 1e-3);
+
+        assert!(Filter::lowpass(250.0, rate).is_err());
+        assert!(Filter::bandpass(12.0, 8.0, rate).is_err());
+    }
+
+    #[test]
+    fn test_filtfilt() {
+        let rate = 250.0;
+        let mut signal = sine(5.0, rate, 2000) + sine(50.0, rate, 2000);
+        let alpha = sine(5.0, rate, 2000);
+
+        Filter::notch(50.0, 4.0, rate)
+            .unwrap()
+            .filtfilt(signal.view_mut());
+        // No phase shift: the 5 Hz component is left in place
+        assert!(rms(&(&signal - &alpha)) < 0.01);
+
+        // A constant offset is removed by a high-pass filter without transient at the edges
+        let mut offset = Array1::from_elem(500, 100.0);
+        Filter::highpass(1.0, rate)
+            .unwrap()
+            .filtfilt(offset.view_mut());
+        assert!(offset.iter().all(|x| x.abs() < 1e-6));
+    }
+
+    #[test]
+    fn test_streaming_filter() {
+        let rate = 250.0;
+        let signal = sine(3.0, rate, 1000) + sine(60.0, rate, 1000);
+        let mut eeg = Array2::from_shape_fn((1000, 2), |(i, c)| signal[i] * (c + 1) as Float);
+        let mut whole = eeg.clone();
+
+        let filter = Filter::lowpass(20.0, rate).unwrap();
+        let mut streaming = filter.streaming();
+        for start in (0..1000).step_by(64) {
+            let end = (start + 64).min(1000);
+            streaming
+                .process(eeg.slice_mut(s![start..end, ..]))
+                .unwrap();
+        }
+        filter.streaming().process(whole.view_mut()).unwrap();
+        assert_eq!(eeg, whole);
+
+        // The 60 Hz component is gone, the 3 Hz one delayed but intact
+        let filtered = eeg.column(0).to_owned();
+        assert!((rms(&filtered) - 0.5_f64.sqrt()).abs() < 0.01);
+        assert!(streaming.process(Array2::zeros((4, 3)).view_mut()).is_err());
+    }
+}
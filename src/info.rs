@@ -1,12 +1,13 @@
-use anyhow::Result;
+use crate::error::Result;
 use chrono::{DateTime, MappedLocalTime, TimeZone, Utc};
 use regex::Regex;
 use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
-use std::io::{self, BufRead};
+use std::io::{self, BufRead, Write};
 
 /// Struct holding device information for EEG data.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct DeviceInfo {
     pub version: String,
     pub start_date: Option<DateTime<Utc>>,
@@ -21,7 +22,7 @@ pub struct DeviceInfo {
 }
 
 /// Struct for EEG settings including sampling rate, filters, and montage.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct EEGSettings {
     pub total_channels: usize,
     pub eeg_channels: usize,
@@ -39,7 +40,7 @@ pub struct EEGSettings {
 }
 
 /// Struct for accelerometer data.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct AccelerometerData {
     pub channels: usize,
     pub sampling_rate: f32,
@@ -47,19 +48,25 @@ pub struct AccelerometerData {
 }
 
 /// Struct for trigger information in EEG data.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct TriggerInfo {
     pub triggers: HashMap<u32, String>,
 }
 
 /// Main struct representing EEG data, including device, settings, and trigger info.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct EEGData {
     pub device_info: DeviceInfo,
     pub eeg_settings: EEGSettings,
     pub trigger_info: TriggerInfo,
 }
 
+impl Default for EEGData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl EEGData {
     /// Creates a new, empty EEGData struct.
     pub fn new() -> Self {
@@ -100,7 +107,16 @@ impl EEGData {
     /// Parses an EEG data file and returns an EEGData struct.
     pub fn parse_file(filename: &str) -> Result<Self> {
         let file = File::open(filename)?;
-        let reader = io::BufReader::new(file);
+        Self::parse_reader(io::BufReader::new(file))
+    }
+
+    /// Parses the content of an `.info` file held in memory.
+    pub fn parse_str(content: &str) -> Result<Self> {
+        Self::parse_reader(content.as_bytes())
+    }
+
+    /// Parses an `.info` file from any buffered reader.
+    pub fn parse_reader<R: BufRead>(reader: R) -> Result<Self> {
         let mut data = EEGData::new();
         let mut current_section = None;
 
@@ -115,7 +131,7 @@ impl EEGData {
                 current_section = Some("Trigger information");
             }
 
-            match current_section.as_deref() {
+            match current_section {
                 Some("Step Details") => Self::parse_step_details(&line, &mut data),
                 Some("EEG Settings") => Self::parse_eeg_settings(&line, &mut data),
                 Some("Trigger information") => Self::parse_trigger_info(&line, &mut data),
@@ -125,18 +141,36 @@ impl EEGData {
         Ok(data)
     }
 
+    /// Returns the electrode names of the montage, ordered by channel number.
+    pub fn electrodes(&self) -> Vec<String> {
+        let mut montage: Vec<(&usize, &String)> = self.eeg_settings.montage.iter().collect();
+        montage.sort_by_key(|(channel, _)| **channel);
+        montage
+            .into_iter()
+            .map(|(_, electrode)| electrode.clone())
+            .collect()
+    }
+
+    /// Writes the metadata as an `.info` file, in the layout read by [`parse_file`](Self::parse_file).
+    pub fn write_file(&self, filename: &str) -> Result<()> {
+        let mut file = io::BufWriter::new(File::create(filename)?);
+        self.write_to(&mut file)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Writes the metadata in the `.info` layout to any writer.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        write!(writer, "{}", self)?;
+        Ok(())
+    }
+
     /// Parses the 'Step Details' section of the file.
     fn parse_step_details(line: &str, data: &mut EEGData) {
         if line.contains("Info Version") {
-            data.device_info.version = line.split(':').nth(1).unwrap_or("").trim().to_string();
+            data.device_info.version = value_of(line).to_string();
         } else if line.contains("StartDate") {
-            let timestamp: i64 = line
-                .split(':')
-                .nth(1)
-                .unwrap_or("")
-                .trim()
-                .parse()
-                .unwrap_or(0);
+            let timestamp: i64 = value_of(line).parse().unwrap_or(0);
 
             data.device_info.start_date = match Utc.timestamp_millis_opt(timestamp) {
                 MappedLocalTime::Single(dt) => Some(dt),
@@ -144,85 +178,40 @@ impl EEGData {
                 MappedLocalTime::None => None,
             }
         } else if line.contains("Device class") {
-            data.device_info.device_class = line.split(':').nth(1).unwrap_or("").trim().to_string();
+            data.device_info.device_class = value_of(line).to_string();
         } else if line.contains("Communication type") {
-            data.device_info.communication_type =
-                line.split(':').nth(1).unwrap_or("").trim().to_string();
+            data.device_info.communication_type = value_of(line).to_string();
         } else if line.contains("Device ID") {
-            data.device_info.device_id = line.split(':').nth(1).unwrap_or("").trim().to_string();
+            data.device_info.device_id = value_of(line).to_string();
         } else if line.contains("Software's version") {
-            data.device_info.software_version =
-                line.split(':').nth(1).unwrap_or("").trim().to_string();
+            data.device_info.software_version = value_of(line).to_string();
         } else if line.contains("Firmware's version") {
-            data.device_info.firmware_version =
-                line.split(':').nth(1).unwrap_or("").trim().to_string();
+            data.device_info.firmware_version = value_of(line).to_string();
         } else if line.contains("Operative system") {
-            data.device_info.os = line.split(':').nth(1).unwrap_or("").trim().to_string();
+            data.device_info.os = value_of(line).to_string();
         } else if line.contains("SDCard Filename") {
-            data.device_info.sdcard_filename =
-                line.split(':').nth(1).unwrap_or("").trim().to_string();
+            data.device_info.sdcard_filename = value_of(line).to_string();
         } else if line.contains("Additional channel") {
-            data.device_info.additional_channel =
-                line.split(':').nth(1).unwrap_or("").trim().to_string();
+            data.device_info.additional_channel = value_of(line).to_string();
         }
     }
 
     /// Parses the 'EEG Settings' section of the file.
     fn parse_eeg_settings(line: &str, data: &mut EEGData) {
         if line.contains("Total number of channels") {
-            data.eeg_settings.total_channels = line
-                .split(':')
-                .nth(1)
-                .unwrap_or("")
-                .trim()
-                .parse()
-                .unwrap_or(0);
+            data.eeg_settings.total_channels = value_of(line).parse().unwrap_or(0);
         } else if line.contains("Number of EEG channels") {
-            data.eeg_settings.eeg_channels = line
-                .split(':')
-                .nth(1)
-                .unwrap_or("")
-                .trim()
-                .parse()
-                .unwrap_or(0);
+            data.eeg_settings.eeg_channels = value_of(line).parse().unwrap_or(0);
         } else if line.contains("Number of records of EEG") {
-            data.eeg_settings.records = line
-                .split(':')
-                .nth(1)
-                .unwrap_or("")
-                .trim()
-                .parse()
-                .unwrap_or(0);
+            data.eeg_settings.records = value_of(line).parse().unwrap_or(0);
         } else if line.contains("EEG sampling rate") {
-            let re = Regex::new(r"(\d+)\s*Samples/second").unwrap();
-
-            // Try to find a match in the input string
-            let sample_rate = if let Some(captures) = re.captures(&line) {
-                // Extract the first capture group and convert it to a f64
-                captures[1].parse::<f32>().ok()
-            } else {
-                None
-            };
-
-            if sample_rate.is_some() {
-                data.eeg_settings.sampling_rate = sample_rate.unwrap();
+            if let Some(sample_rate) = sampling_rate_of(line) {
+                data.eeg_settings.sampling_rate = sample_rate;
             }
         } else if line.contains("EEG recording configured duration") {
-            data.eeg_settings.configured_duration = line
-                .split(':')
-                .nth(1)
-                .unwrap_or("")
-                .trim()
-                .parse()
-                .unwrap_or(0);
+            data.eeg_settings.configured_duration = value_of(line).parse().unwrap_or(0);
         } else if line.contains("Number of packets lost") {
-            data.eeg_settings.packets_lost = line
-                .split(':')
-                .nth(1)
-                .unwrap_or("")
-                .trim()
-                .parse()
-                .unwrap_or(0);
+            data.eeg_settings.packets_lost = value_of(line).parse().unwrap_or(0);
         } else if line.contains("Line filter status") {
             data.eeg_settings.line_filter = line.contains("ON");
         } else if line.contains("FIR filter status") {
@@ -232,9 +221,10 @@ impl EEGData {
         } else if line.contains("Reference filter status") {
             data.eeg_settings.reference_filter = line.contains("ON");
         } else if line.contains("EEG units") {
-            data.eeg_settings.eeg_units = line.split(':').nth(1).unwrap_or("").trim().to_string();
+            data.eeg_settings.eeg_units = value_of(line).to_string();
         } else if line.contains("Accelerometer data") {
             if line.contains("ON") {
+                // Defaults of NIC, refined by the lines that may follow
                 let accelerometer = AccelerometerData {
                     channels: 3,
                     sampling_rate: 100.0,
@@ -242,17 +232,33 @@ impl EEGData {
                 };
                 data.eeg_settings.accelerometer = Some(accelerometer);
             }
+        } else if line.contains("Number of accelerometer channels") {
+            if let Some(accelerometer) = &mut data.eeg_settings.accelerometer {
+                accelerometer.channels = value_of(line).parse().unwrap_or(accelerometer.channels);
+            }
+        } else if line.contains("Accelerometer sampling rate") {
+            if let (Some(accelerometer), Some(sampling_rate)) =
+                (&mut data.eeg_settings.accelerometer, sampling_rate_of(line))
+            {
+                accelerometer.sampling_rate = sampling_rate;
+            }
+        } else if line.contains("Accelerometer units") {
+            if let Some(accelerometer) = &mut data.eeg_settings.accelerometer {
+                accelerometer.units = value_of(line).to_string();
+            }
         } else if line.contains("Channel") {
-            let parts: Vec<&str> = line.split(':').collect();
-            let channel_number = parts[0]
-                .split_whitespace()
-                .nth(1)
-                .unwrap_or("")
-                .trim()
-                .parse()
-                .unwrap_or(0);
-            let electrode = parts[1].trim().to_string();
-            data.eeg_settings.montage.insert(channel_number, electrode);
+            // Lines without a `Channel N: name` shape are ignored.
+            if let Some((channel, electrode)) = line.split_once(':') {
+                let channel_number = channel
+                    .split_whitespace()
+                    .nth(1)
+                    .unwrap_or("")
+                    .trim()
+                    .parse()
+                    .unwrap_or(0);
+                let electrode = electrode.trim().to_string();
+                data.eeg_settings.montage.insert(channel_number, electrode);
+            }
         }
     }
 
@@ -275,10 +281,116 @@ impl EEGData {
     }
 }
 
+/// Returns the value of a `Key: value` line, everything after the first colon.
+fn value_of(line: &str) -> &str {
+    line.split_once(':').map_or("", |(_, value)| value).trim()
+}
+
+/// Returns the rate of a `Key: 500 Samples/second` or `Key: 500.0` line.
+fn sampling_rate_of(line: &str) -> Option<f32> {
+    let re = Regex::new(r":\s*(\d+(?:\.\d+)?)").unwrap();
+    re.captures(line)
+        .and_then(|captures| captures[1].parse::<f32>().ok())
+}
+
+/// Renders an ON/OFF status flag.
+fn status(on: bool) -> &'static str {
+    if on {
+        "ON"
+    } else {
+        "OFF"
+    }
+}
+
+/// Renders the `.info` file, one `Key: value` line per field, grouped in the sections
+/// `Step Details`, `EEG Settings` and `Trigger information`.
+impl fmt::Display for EEGData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let device = &self.device_info;
+        writeln!(f, "Step Details")?;
+        writeln!(f, "Info Version: {}", device.version)?;
+        if let Some(start_date) = device.start_date {
+            writeln!(f, "StartDate: {}", start_date.timestamp_millis())?;
+        }
+        writeln!(f, "Device class: {}", device.device_class)?;
+        writeln!(f, "Communication type: {}", device.communication_type)?;
+        writeln!(f, "Device ID: {}", device.device_id)?;
+        writeln!(f, "Software's version: {}", device.software_version)?;
+        writeln!(f, "Firmware's version: {}", device.firmware_version)?;
+        writeln!(f, "Operative system: {}", device.os)?;
+        writeln!(f, "SDCard Filename: {}", device.sdcard_filename)?;
+        writeln!(f, "Additional channel: {}", device.additional_channel)?;
+        writeln!(f)?;
+
+        let settings = &self.eeg_settings;
+        writeln!(f, "EEG Settings")?;
+        writeln!(f, "Total number of channels: {}", settings.total_channels)?;
+        writeln!(f, "Number of EEG channels: {}", settings.eeg_channels)?;
+        writeln!(f, "Number of records of EEG: {}", settings.records)?;
+        writeln!(
+            f,
+            "EEG sampling rate: {} Samples/second",
+            settings.sampling_rate
+        )?;
+        writeln!(
+            f,
+            "EEG recording configured duration: {}",
+            settings.configured_duration
+        )?;
+        writeln!(f, "Number of packets lost: {}", settings.packets_lost)?;
+        writeln!(f, "Line filter status: {}", status(settings.line_filter))?;
+        writeln!(f, "FIR filter status: {}", status(settings.fir_filter))?;
+        writeln!(
+            f,
+            "EOG correction filter status: {}",
+            status(settings.eog_correction)
+        )?;
+        writeln!(
+            f,
+            "Reference filter status: {}",
+            status(settings.reference_filter)
+        )?;
+        writeln!(f, "EEG units: {}", settings.eeg_units)?;
+        writeln!(
+            f,
+            "Accelerometer data: {}",
+            status(settings.accelerometer.is_some())
+        )?;
+        if let Some(accelerometer) = &settings.accelerometer {
+            writeln!(
+                f,
+                "Number of accelerometer channels: {}",
+                accelerometer.channels
+            )?;
+            writeln!(
+                f,
+                "Accelerometer sampling rate: {} Samples/second",
+                accelerometer.sampling_rate
+            )?;
+            writeln!(f, "Accelerometer units: {}", accelerometer.units)?;
+        }
+        let mut montage: Vec<(&usize, &String)> = settings.montage.iter().collect();
+        montage.sort_by_key(|(channel, _)| **channel);
+        for (channel, electrode) in montage {
+            writeln!(f, "Channel {}: {}", channel, electrode)?;
+        }
+        writeln!(f)?;
+
+        writeln!(f, "Trigger information")?;
+        writeln!(f, "Code Description")?;
+        let mut triggers: Vec<(&u32, &String)> = self.trigger_info.triggers.iter().collect();
+        triggers.sort_by_key(|(code, _)| **code);
+        for (code, description) in triggers {
+            writeln!(f, "{} {}", code, description)?;
+        }
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::TimeZone;
 
     // Helper function to create a sample EEG file.
     fn create_sample_file() -> String {
@@ -451,4 +563,38 @@ mod tests {
 
         std::fs::remove_file(filename).unwrap();
     }
+
+    // Test writing an EEG data file and parsing it back
+    #[test]
+    fn test_write_round_trip() {
+        let filename = create_sample_file();
+        let mut eeg_data = EEGData::parse_file(&filename).unwrap();
+        std::fs::remove_file(filename).unwrap();
+
+        // Values with colons must survive the round trip
+        eeg_data.device_info.sdcard_filename = "C:\\NIC\\eeg_data.easy".to_string();
+        eeg_data.eeg_settings.sampling_rate = 250.5;
+        eeg_data
+            .trigger_info
+            .triggers
+            .insert(12, "Stimulus: left hand".to_string());
+        eeg_data.eeg_settings.accelerometer = Some(AccelerometerData {
+            channels: 2,
+            sampling_rate: 500.0,
+            units: "g".to_string(),
+        });
+
+        let filename = "round_trip_eeg_data.info";
+        eeg_data.write_file(filename).unwrap();
+        let parsed = EEGData::parse_file(filename).unwrap();
+        assert_eq!(parsed, eeg_data);
+        std::fs::remove_file(filename).unwrap();
+
+        // Accelerometer OFF and an empty trigger table
+        eeg_data.eeg_settings.accelerometer = None;
+        eeg_data.trigger_info.triggers.clear();
+        eeg_data.device_info.start_date = None;
+        let parsed = EEGData::parse_str(&eeg_data.to_string()).unwrap();
+        assert_eq!(parsed, eeg_data);
+    }
 }
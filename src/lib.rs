@@ -1,2 +1,20 @@
+pub mod bids;
+pub mod brainvision;
+#[cfg(feature = "arrow")]
+pub mod columnar;
+pub mod easy_reader;
+pub mod easy_writer;
+pub mod edf;
+pub mod epochs;
+pub mod erp;
+pub mod error;
+pub mod filter;
 pub mod info;
-pub mod easy_reader;
\ No newline at end of file
+pub mod line_noise;
+pub mod mat;
+pub mod nedf;
+pub mod npy;
+pub mod recording;
+#[cfg(test)]
+mod test_support;
+pub mod validate;
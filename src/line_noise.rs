@@ -0,0 +1,281 @@
+use crate::easy_reader::Float;
+use crate::error::{EasyError, Result};
+use crate::filter::Filter;
+use crate::recording::Recording;
+use chrono::Utc;
+use ndarray::{Array1, ArrayView1, ArrayView2, ArrayViewMut2, Axis};
+use std::f64::consts::PI;
+
+/// Mains frequencies considered by [`detect_line_frequency`], in Hz.
+pub const LINE_FREQUENCIES: [Float; 2] = [50.0, 60.0];
+
+/// Settings of [`remove_line_noise`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineNoiseOptions {
+    /// Mains frequency in Hz, detected from the data when `None`.
+    pub frequency: Option<Float>,
+    /// Width in Hz of the notch around the fundamental and each harmonic.
+    pub bandwidth: Float,
+    /// Highest multiple of the fundamental removed, all those below the Nyquist frequency
+    /// when `None`.
+    pub max_harmonic: Option<usize>,
+}
+
+impl Default for LineNoiseOptions {
+    fn default() -> Self {
+        LineNoiseOptions {
+            frequency: None,
+            bandwidth: 2.0,
+            max_harmonic: None,
+        }
+    }
+}
+
+impl LineNoiseOptions {
+    /// Removes noise at `frequency` instead of detecting it.
+    pub fn with_frequency(mut self, frequency: Float) -> Self {
+        self.frequency = Some(frequency);
+        self
+    }
+}
+
+/// What [`remove_line_noise`] removed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineNoiseReport {
+    /// Mains frequency, in Hz.
+    pub frequency: Float,
+    /// `true` if the frequency was detected from the data rather than given.
+    pub detected: bool,
+    /// Frequencies notched out, the fundamental and its harmonics, in Hz.
+    pub notches: Vec<Float>,
+    /// Mean power of the signal removed from each channel, in the squared unit of the channels.
+    pub removed_power: Array1<Float>,
+    /// Part of the variance of each channel removed, between `0` and `1` for line noise.
+    pub removed_fraction: Array1<Float>,
+}
+
+/// Power of the sinusoid at `frequency` best fitting a signal, once its mean is removed.
+fn sinusoid_power(signal: ArrayView1<Float>, frequency: Float, sampling_rate: Float) -> Float {
+    let num_samples = signal.len();
+    if num_samples == 0 {
+        return 0.0;
+    }
+    let mean = signal.sum() / num_samples as Float;
+    let w = 2.0 * PI * frequency / sampling_rate;
+    let (cos, sin) = signal
+        .iter()
+        .enumerate()
+        .fold((0.0, 0.0), |(cos, sin), (i, x)| {
+            let phase = w * i as Float;
+            (
+                cos + (x - mean) * phase.cos(),
+                sin + (x - mean) * phase.sin(),
+            )
+        });
+    let scale = 2.0 / num_samples as Float;
+    ((cos * scale).powi(2) + (sin * scale).powi(2)) / 2.0
+}
+
+/// Returns the frequency of [`LINE_FREQUENCIES`] with the most power summed over the channels of
+/// `eeg`, shape `(num_samples, num_channels)`.
+///
+/// Only frequencies below the Nyquist frequency are considered, `None` if there are none.
+pub fn detect_line_frequency(eeg: ArrayView2<Float>, sampling_rate: Float) -> Option<Float> {
+    LINE_FREQUENCIES
+        .into_iter()
+        .filter(|frequency| *frequency < sampling_rate / 2.0)
+        .map(|frequency| {
+            let power: Float = eeg
+                .columns()
+                .into_iter()
+                .map(|column| sinusoid_power(column, frequency, sampling_rate))
+                .sum();
+            (frequency, power)
+        })
+        .fold(None, |best: Option<(Float, Float)>, candidate| match best {
+            Some(best) if best.1 >= candidate.1 => Some(best),
+            _ => Some(candidate),
+        })
+        .map(|(frequency, _)| frequency)
+}
+
+/// Removes the mains noise from every channel of `eeg`, shape `(num_samples, num_channels)`, in
+/// place.
+///
+/// Unless given, the mains frequency is the one of [`LINE_FREQUENCIES`] that dominates the data.
+/// The fundamental and its harmonics whose notch fits below the Nyquist frequency are removed
+/// with zero-phase [`Filter::notch`] filters.
+pub fn remove_line_noise(
+    mut eeg: ArrayViewMut2<Float>,
+    sampling_rate: Float,
+    options: &LineNoiseOptions,
+) -> Result<LineNoiseReport> {
+    let nyquist = sampling_rate / 2.0;
+    let (frequency, detected) = match options.frequency {
+        Some(frequency) => (frequency, false),
+        None => (
+            detect_line_frequency(eeg.view(), sampling_rate).ok_or_else(|| {
+                EasyError::Unsupported(format!(
+                    "no mains frequency below the Nyquist frequency of {} Hz",
+                    nyquist
+                ))
+            })?,
+            true,
+        ),
+    };
+
+    if !(frequency.is_finite() && frequency > 0.0) {
+        return Err(EasyError::Unsupported(format!(
+            "invalid mains frequency of {} Hz",
+            frequency
+        )));
+    }
+    if !(options.bandwidth.is_finite() && options.bandwidth > 0.0) {
+        return Err(EasyError::Unsupported(format!(
+            "invalid notch bandwidth of {} Hz",
+            options.bandwidth
+        )));
+    }
+
+    let max_harmonic = options.max_harmonic.unwrap_or(usize::MAX);
+    let notches: Vec<Float> = (1..=max_harmonic)
+        .map(|k| k as Float * frequency)
+        .take_while(|notch| notch + options.bandwidth / 2.0 < nyquist)
+        .collect();
+    if notches.is_empty() {
+        return Err(EasyError::Unsupported(format!(
+            "no notch of {} Hz around {} Hz fits below the Nyquist frequency of {} Hz",
+            options.bandwidth, frequency, nyquist
+        )));
+    }
+    let filters = notches
+        .iter()
+        .map(|notch| Filter::notch(*notch, options.bandwidth, sampling_rate))
+        .collect::<Result<Vec<_>>>()?;
+
+    let original = eeg.to_owned();
+    for filter in &filters {
+        filter.filtfilt_columns(eeg.view_mut());
+    }
+
+    let removed_power = (&original - &eeg)
+        .mapv(|x| x * x)
+        .mean_axis(Axis(0))
+        .unwrap_or_else(|| Array1::zeros(eeg.ncols()));
+    let variance = original.var_axis(Axis(0), 0.0);
+    let removed_fraction = ndarray::Zip::from(&removed_power)
+        .and(&variance)
+        .map_collect(|removed, variance| {
+            if *variance > 0.0 {
+                removed / variance
+            } else {
+                0.0
+            }
+        });
+
+    Ok(LineNoiseReport {
+        frequency,
+        detected,
+        notches,
+        removed_power,
+        removed_fraction,
+    })
+}
+
+impl Recording {
+    /// Removes the mains noise from every EEG channel in place, see [`remove_line_noise`].
+    ///
+    /// Useful when the line filter of the device was OFF, see
+    /// [`EEGSettings::line_filter`](crate::info::EEGSettings::line_filter).
+    pub fn remove_line_noise(&mut self, options: &LineNoiseOptions) -> Result<LineNoiseReport> {
+        let report = remove_line_noise(self.eeg.view_mut(), self.sampling_rate, options)?;
+        self.log.push(format!(
+            "line noise removed at {} Hz ({}), notches at {:?} Hz: {}",
+            report.frequency,
+            if report.detected { "detected" } else { "given" },
+            report.notches,
+            Utc::now()
+        ));
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::SampleRecording;
+    use ndarray::{s, Array2};
+
+    fn sine(frequency: Float, sampling_rate: Float, i: usize) -> Float {
+        (2.0 * PI * frequency * i as Float / sampling_rate).sin()
+    }
+
+    #[test]
+    fn test_remove_line_noise() {
+        let rate = 500.0;
+        // 10 Hz activity, 50 Hz noise growing with the channel, and its third harmonic
+        let clean = Array2::from_shape_fn((10000, 3), |(i, _)| 20.0 * sine(10.0, rate, i));
+        let mut eeg = Array2::from_shape_fn((10000, 3), |(i, c)| {
+            clean[[i, c]] + 10.0 * c as Float * sine(50.0, rate, i) + 2.0 * sine(150.0, rate, i)
+        });
+
+        let report = remove_line_noise(eeg.view_mut(), rate, &LineNoiseOptions::default()).unwrap();
+        assert_eq!(report.frequency, 50.0);
+        assert!(report.detected);
+        assert_eq!(report.notches, vec![50.0, 100.0, 150.0, 200.0]);
+        for c in 0..3 {
+            let expected = (10.0 * c as Float).powi(2) / 2.0 + 2.0;
+            assert!((report.removed_power[c] - expected).abs() < 0.05 * expected);
+        }
+        // Half of the variance of the last channel is line noise
+        assert!((report.removed_fraction[2] - 0.5).abs() < 0.03);
+
+        // The 10 Hz activity is left, away from the edges
+        let residual = &eeg.slice(s![1000..9000, ..]) - &clean.slice(s![1000..9000, ..]);
+        assert!(residual.iter().all(|x| x.abs() < 0.1));
+
+        let options = LineNoiseOptions {
+            max_harmonic: Some(1),
+            ..LineNoiseOptions::default()
+        };
+        assert!(remove_line_noise(eeg.view_mut(), 90.0, &options).is_err());
+
+        // Invalid settings are rejected rather than notching forever
+        for frequency in [0.0, -50.0, Float::NAN, Float::INFINITY] {
+            let options = LineNoiseOptions::default().with_frequency(frequency);
+            assert!(remove_line_noise(eeg.view_mut(), rate, &options).is_err());
+        }
+        for bandwidth in [0.0, -2.0, Float::NAN] {
+            let options = LineNoiseOptions {
+                bandwidth,
+                ..LineNoiseOptions::default()
+            };
+            assert!(remove_line_noise(eeg.view_mut(), rate, &options).is_err());
+        }
+    }
+
+    #[test]
+    fn test_recording_line_noise() {
+        let rate = 250.0;
+        let mut recording = SampleRecording::new(rate, 5000)
+            .eeg(|i, _| (1000.0 * (5.0 * sine(7.0, 250.0, i) + 8.0 * sine(60.0, 250.0, i))) as i64)
+            .settings("Line filter status: OFF\n")
+            .recording();
+
+        assert_eq!(
+            detect_line_frequency(recording.eeg.view(), rate),
+            Some(60.0)
+        );
+        let report = recording
+            .remove_line_noise(&LineNoiseOptions::default().with_frequency(60.0))
+            .unwrap();
+        assert!(!report.detected);
+        assert_eq!(report.notches, vec![60.0, 120.0]);
+        assert!(report.removed_power.iter().all(|p| (p - 32.0).abs() < 1.6));
+        assert!(recording
+            .log
+            .last()
+            .unwrap()
+            .starts_with("line noise removed at 60 Hz"));
+    }
+}
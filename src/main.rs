@@ -17,12 +17,22 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // println!("{reader:#?}");
 
     // a streaming example
-    reader.stream(Some(10000), |eeg_chunk, acc_chunk, markers_chunk| {
+    reader.stream(Some(10000), |chunk| {
         // Process the chunk, for example, you could print the first few samples or store them
-        println!("Processing chunk of size: {}", eeg_chunk.len());
-        println!("First EEG sample: {:?}", eeg_chunk.first());
-        println!("First Acc sample: {:?}", acc_chunk.first());
-        println!("First Marker: {:?}", markers_chunk.first());
+        println!("Processing chunk of size: {}", chunk.len());
+        println!(
+            "First EEG sample: {:?}",
+            chunk.eeg.rows().into_iter().next()
+        );
+        println!(
+            "First Acc sample: {:?}",
+            chunk
+                .acc
+                .as_ref()
+                .and_then(|acc| acc.rows().into_iter().next())
+        );
+        println!("First Marker: {:?}", chunk.markers.first());
+        println!("First Timestamp: {:?}", chunk.timestamps.first());
     })?;
 
     Ok(())
@@ -0,0 +1,396 @@
+use crate::easy_reader::Float;
+use crate::error::{EasyError, Result};
+use crate::recording::Recording;
+use chrono::Utc;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Data types of MAT-file elements.
+const MI_INT8: u32 = 1;
+const MI_UINT16: u32 = 4;
+const MI_INT32: u32 = 5;
+const MI_UINT32: u32 = 6;
+const MI_DOUBLE: u32 = 9;
+const MI_MATRIX: u32 = 14;
+
+/// Array classes of MAT-file matrices.
+const MX_STRUCT_CLASS: u32 = 2;
+const MX_CHAR_CLASS: u32 = 4;
+const MX_DOUBLE_CLASS: u32 = 6;
+
+/// Longest struct field name, NUL terminator included.
+const FIELD_NAME_LENGTH: usize = 32;
+
+/// A MATLAB value, as stored in a MAT-file.
+enum Value {
+    /// A real matrix, values in column-major order.
+    Double {
+        rows: usize,
+        data: Vec<Float>,
+    },
+    Char(String),
+    /// A `1×n` struct array, one list of values per element, in the order of the fields.
+    Struct {
+        fields: Vec<&'static str>,
+        elements: Vec<Vec<Value>>,
+    },
+}
+
+impl Value {
+    fn scalar(value: Float) -> Self {
+        Value::Double {
+            rows: 1,
+            data: vec![value],
+        }
+    }
+
+    fn empty() -> Self {
+        Value::Double {
+            rows: 0,
+            data: Vec::new(),
+        }
+    }
+
+    fn text(value: &str) -> Self {
+        Value::Char(value.to_string())
+    }
+
+    /// A `1×1` struct.
+    fn record(fields: Vec<(&'static str, Value)>) -> Self {
+        let (fields, values) = fields.into_iter().unzip();
+        Value::Struct {
+            fields,
+            elements: vec![values],
+        }
+    }
+}
+
+/// Writes a recording as a MATLAB `.mat` file holding an EEGLAB `EEG` struct.
+///
+/// See [`write_mat_to`] for the content of the struct.
+pub fn write_mat(recording: &Recording, filename: &str) -> Result<()> {
+    let mut file = BufWriter::new(File::create(filename)?);
+    write_mat_to(recording, &mut file)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Writes a recording as a MAT-file (version 5) to any writer.
+///
+/// The file holds a single variable, `EEG`, laid out as an EEGLAB dataset: `data` is the
+/// `nbchan×pnts` EEG in the unit of the channels, `srate` the sampling rate,
+/// `chanlocs(i).labels` the channel names, and `event` one entry per event with its 1-based
+/// `latency` in samples, `duration` in samples and `type` from the trigger table, or the marker
+/// code when it has no entry. EEGLAB fills in the remaining fields when the dataset is loaded:
+///
+/// ```matlab
+/// load('Example01.mat');
+/// EEG = eeg_checkset(EEG);
+/// ```
+pub fn write_mat_to<W: Write>(recording: &Recording, writer: &mut W) -> Result<()> {
+    let sampling_rate = recording.sampling_rate;
+    let (num_samples, num_channels) = recording.eeg.dim();
+
+    let chanlocs = Value::Struct {
+        fields: vec!["labels", "type"],
+        elements: recording
+            .channels
+            .iter()
+            .map(|channel| vec![Value::text(&channel.name), Value::text("EEG")])
+            .collect(),
+    };
+
+    let event = Value::Struct {
+        fields: vec!["type", "latency", "duration"],
+        elements: recording
+            .events
+            .iter()
+            .map(|event| {
+                vec![
+                    Value::Char(event.label()),
+                    Value::scalar((event.sample_index + 1) as Float),
+                    Value::scalar(event.duration as Float),
+                ]
+            })
+            .collect(),
+    };
+
+    let path = Path::new(&recording.source);
+    let filename = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("");
+    let directory = path
+        .parent()
+        .and_then(|parent| parent.to_str())
+        .unwrap_or("");
+    let comments = format!(
+        "Converted by easy-rs, start date {}, units {}",
+        recording.start_time.to_rfc3339(),
+        recording.eeg_unit()
+    );
+
+    // Row-major samples×channels is column-major channels×samples
+    let data = Value::Double {
+        rows: num_channels,
+        data: recording.eeg.iter().copied().collect(),
+    };
+
+    let dataset = Value::record(vec![
+        (
+            "setname",
+            Value::text(filename.split('.').next().unwrap_or("")),
+        ),
+        ("filename", Value::text(filename)),
+        ("filepath", Value::text(directory)),
+        ("subject", Value::text("")),
+        ("group", Value::text("")),
+        ("condition", Value::text("")),
+        ("session", Value::empty()),
+        ("comments", Value::Char(comments)),
+        ("nbchan", Value::scalar(num_channels as Float)),
+        ("trials", Value::scalar(1.0)),
+        ("pnts", Value::scalar(num_samples as Float)),
+        ("srate", Value::scalar(sampling_rate)),
+        ("xmin", Value::scalar(0.0)),
+        (
+            "xmax",
+            Value::scalar(num_samples.saturating_sub(1) as Float / sampling_rate),
+        ),
+        ("times", Value::empty()),
+        ("data", data),
+        ("icaact", Value::empty()),
+        ("icawinv", Value::empty()),
+        ("icasphere", Value::empty()),
+        ("icaweights", Value::empty()),
+        ("icachansind", Value::empty()),
+        ("chanlocs", chanlocs),
+        ("urchanlocs", Value::empty()),
+        ("chaninfo", Value::empty()),
+        ("ref", Value::text("common")),
+        ("event", event),
+        ("urevent", Value::empty()),
+        ("eventdescription", Value::empty()),
+        ("epoch", Value::empty()),
+        ("epochdescription", Value::empty()),
+        ("saved", Value::text("no")),
+    ]);
+
+    // 116 bytes of text, 8 bytes of subsystem offset, the version and the endianness indicator
+    let mut header = format!(
+        "MATLAB 5.0 MAT-file, Platform: easy-rs, Created on: {}",
+        Utc::now().format("%a %b %e %H:%M:%S %Y")
+    )
+    .into_bytes();
+    header.resize(116, b' ');
+    header.extend_from_slice(&[0; 8]);
+    header.extend_from_slice(&0x0100u16.to_le_bytes());
+    header.extend_from_slice(b"IM");
+    writer.write_all(&header)?;
+
+    writer.write_all(&matrix("EEG", &dataset)?)?;
+    Ok(())
+}
+
+/// Encodes a value as a `miMATRIX` element.
+fn matrix(name: &str, value: &Value) -> Result<Vec<u8>> {
+    let mut content = Vec::new();
+    match value {
+        Value::Double { rows, data } => {
+            let columns = if *rows == 0 { 0 } else { data.len() / rows };
+            content.extend(array_header(MX_DOUBLE_CLASS, *rows, columns, name));
+            let bytes: Vec<u8> = data.iter().flat_map(|x| x.to_le_bytes()).collect();
+            content.extend(element(MI_DOUBLE, &bytes)?);
+        }
+        Value::Char(text) => {
+            let units: Vec<u16> = text.encode_utf16().collect();
+            let rows = usize::from(!units.is_empty());
+            content.extend(array_header(MX_CHAR_CLASS, rows, units.len(), name));
+            let bytes: Vec<u8> = units.iter().flat_map(|unit| unit.to_le_bytes()).collect();
+            content.extend(element(MI_UINT16, &bytes)?);
+        }
+        Value::Struct { fields, elements } => {
+            content.extend(array_header(MX_STRUCT_CLASS, 1, elements.len(), name));
+            content.extend(element(
+                MI_INT32,
+                &(FIELD_NAME_LENGTH as i32).to_le_bytes(),
+            )?);
+            let mut names = vec![0u8; fields.len() * FIELD_NAME_LENGTH];
+            for (i, field) in fields.iter().enumerate() {
+                names[i * FIELD_NAME_LENGTH..][..field.len()].copy_from_slice(field.as_bytes());
+            }
+            content.extend(element(MI_INT8, &names)?);
+            for values in elements {
+                for value in values {
+                    content.extend(matrix("", value)?);
+                }
+            }
+        }
+    }
+    element(MI_MATRIX, &content)
+}
+
+/// Encodes the array flags, dimensions and name subelements of a matrix.
+fn array_header(class: u32, rows: usize, columns: usize, name: &str) -> Vec<u8> {
+    let mut flags = class.to_le_bytes().to_vec();
+    flags.extend_from_slice(&0u32.to_le_bytes());
+    let mut dimensions = (rows as i32).to_le_bytes().to_vec();
+    dimensions.extend_from_slice(&(columns as i32).to_le_bytes());
+
+    // These elements are small, their size always fits
+    let mut header = element(MI_UINT32, &flags).unwrap_or_default();
+    header.extend(element(MI_INT32, &dimensions).unwrap_or_default());
+    header.extend(element(MI_INT8, name.as_bytes()).unwrap_or_default());
+    header
+}
+
+/// Encodes a data element: its type, its size, and its data padded to 8 bytes.
+fn element(data_type: u32, data: &[u8]) -> Result<Vec<u8>> {
+    let size = u32::try_from(data.len()).map_err(|_| {
+        EasyError::Unsupported("MAT-file v5 elements are limited to 4 GiB".to_string())
+    })?;
+    let mut bytes = Vec::with_capacity(8 + data.len() + 7);
+    bytes.extend_from_slice(&data_type.to_le_bytes());
+    bytes.extend_from_slice(&size.to_le_bytes());
+    bytes.extend_from_slice(data);
+    bytes.resize(bytes.len().next_multiple_of(8), 0);
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::SampleRecording;
+
+    /// A decoded `miMATRIX` element.
+    #[derive(Debug)]
+    enum Decoded {
+        Double(Vec<usize>, Vec<Float>),
+        Char(String),
+        Struct(Vec<String>, Vec<Vec<Decoded>>),
+    }
+
+    impl Decoded {
+        fn field(&self, element: usize, name: &str) -> &Decoded {
+            let Decoded::Struct(fields, elements) = self else {
+                panic!("not a struct: {:?}", self);
+            };
+            let index = fields.iter().position(|field| field == name).unwrap();
+            &elements[element][index]
+        }
+    }
+
+    /// Reads a data element, returning its type, its data and the rest of the bytes.
+    fn read_element(bytes: &[u8]) -> (u32, &[u8], &[u8]) {
+        let data_type = u32::from_le_bytes(bytes[..4].try_into().unwrap());
+        let size = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        let padded = (8 + size).next_multiple_of(8);
+        (data_type, &bytes[8..8 + size], &bytes[padded..])
+    }
+
+    fn decode(bytes: &[u8]) -> (String, Decoded) {
+        let (data_type, content, _) = read_element(bytes);
+        assert_eq!(data_type, MI_MATRIX);
+        let (_, flags, rest) = read_element(content);
+        let (_, dimensions, rest) = read_element(rest);
+        let (_, name, rest) = read_element(rest);
+        let dimensions: Vec<usize> = dimensions
+            .chunks(4)
+            .map(|d| i32::from_le_bytes(d.try_into().unwrap()) as usize)
+            .collect();
+        let name = String::from_utf8(name.to_vec()).unwrap();
+
+        let value = match flags[0] as u32 {
+            MX_DOUBLE_CLASS => {
+                let (_, data, _) = read_element(rest);
+                let data = data
+                    .chunks(8)
+                    .map(|x| f64::from_le_bytes(x.try_into().unwrap()))
+                    .collect();
+                Decoded::Double(dimensions, data)
+            }
+            MX_CHAR_CLASS => {
+                let (_, data, _) = read_element(rest);
+                let units: Vec<u16> = data
+                    .chunks(2)
+                    .map(|x| u16::from_le_bytes([x[0], x[1]]))
+                    .collect();
+                Decoded::Char(String::from_utf16(&units).unwrap())
+            }
+            MX_STRUCT_CLASS => {
+                let (_, length, rest) = read_element(rest);
+                let length = i32::from_le_bytes(length.try_into().unwrap()) as usize;
+                let (_, names, mut rest) = read_element(rest);
+                let fields: Vec<String> = names
+                    .chunks(length)
+                    .map(|name| {
+                        String::from_utf8_lossy(name)
+                            .trim_end_matches('\0')
+                            .to_string()
+                    })
+                    .collect();
+                let mut elements = Vec::new();
+                for _ in 0..dimensions[1] {
+                    let mut values = Vec::new();
+                    for _ in &fields {
+                        values.push(decode(rest).1);
+                        rest = read_element(rest).2;
+                    }
+                    elements.push(values);
+                }
+                Decoded::Struct(fields, elements)
+            }
+            class => panic!("unexpected class {}", class),
+        };
+        (name, value)
+    }
+
+    #[test]
+    fn test_write_mat() {
+        let recording = SampleRecording::new(250.0, 6)
+            .eeg(|i, c| {
+                if c == 0 {
+                    i as i64 * 1000
+                } else {
+                    c as i64 * 1000
+                }
+            })
+            .markers(&[(2, 4), (3, 4)])
+            .montage(&["Cz", "Pz", "Oz", "Fz", "C3", "C4", "P3", "P4"])
+            .triggers(&[(4, "Oddball")])
+            .recording();
+
+        let mut bytes = Vec::new();
+        write_mat_to(&recording, &mut bytes).unwrap();
+        assert!(bytes.starts_with(b"MATLAB 5.0 MAT-file"));
+        assert_eq!(&bytes[124..128], &[0x00, 0x01, b'I', b'M']);
+
+        let (name, eeg) = decode(&bytes[128..]);
+        assert_eq!(name, "EEG");
+        let Decoded::Double(dimensions, data) = eeg.field(0, "data") else {
+            panic!("data is not a matrix");
+        };
+        assert_eq!(dimensions, &vec![8, 6]);
+        // Column-major: the second column is the second sample
+        assert_eq!(&data[8..10], &[1.0, 1.0]);
+        assert!(matches!(eeg.field(0, "srate"), Decoded::Double(_, rate) if rate == &vec![250.0]));
+
+        let chanlocs = eeg.field(0, "chanlocs");
+        assert!(matches!(chanlocs.field(0, "labels"), Decoded::Char(label) if label == "Cz"));
+        assert!(matches!(chanlocs.field(7, "labels"), Decoded::Char(label) if label == "P4"));
+
+        let event = eeg.field(0, "event");
+        let Decoded::Struct(_, events) = event else {
+            panic!("event is not a struct");
+        };
+        assert_eq!(events.len(), 1);
+        assert!(matches!(event.field(0, "type"), Decoded::Char(kind) if kind == "Oddball"));
+        assert!(
+            matches!(event.field(0, "latency"), Decoded::Double(_, latency) if latency == &vec![3.0])
+        );
+        assert!(
+            matches!(event.field(0, "duration"), Decoded::Double(_, duration) if duration == &vec![2.0])
+        );
+    }
+}
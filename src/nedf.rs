@@ -0,0 +1,374 @@
+use crate::easy_reader::{scaled_units, EasyParts, Float};
+use crate::error::{EasyError, Result};
+use crate::info::{AccelerometerData, EEGData};
+use crate::recording::{Accelerometer, Channel, ChannelKind, Recording};
+use chrono::{DateTime, Utc};
+use ndarray::Array2;
+use roxmltree::Node;
+use std::fs::File;
+use std::io::{BufReader, Read};
+
+/// Size of the XML header, padded with NUL bytes.
+const HEADER_LENGTH: usize = 10240;
+
+/// Number of EEG samples in each data block, which holds one accelerometer sample.
+const SAMPLES_PER_BLOCK: usize = 5;
+
+/// Value of one step of the 24-bit ADC, in nanovolts.
+const NANOVOLTS_PER_STEP: Float = 2.4e9 / (6.0 * 8388607.0);
+
+/// Layout of the binary data, as described by the XML header.
+#[derive(Debug)]
+struct Layout {
+    num_channels: usize,
+    num_acc_channels: usize,
+    num_samples: usize,
+    /// Stimulation samples interleaved with the EEG, which are skipped.
+    has_stim: bool,
+    /// Markers are 32-bit big-endian integers since version 1.3, single bytes before.
+    marker_bytes: usize,
+}
+
+/// Reads a `.nedf` recording.
+///
+/// See [`read_nedf_from`].
+pub fn read_nedf(filename: &str, scale: Float) -> Result<Recording> {
+    let mut recording = read_nedf_from(BufReader::new(File::open(filename)?), scale)?;
+    recording.source = filename.to_string();
+    Ok(recording)
+}
+
+/// Reads a `.nedf` recording from any reader.
+///
+/// The XML header is mapped onto the same [`EEGData`] model as an `.info` file: the EEG
+/// settings, the montage, the device class and the start date. The binary payload is decoded
+/// as follows:
+///
+/// - the 24-bit EEG is converted to nanovolts and divided by `scale`, like `.easy` data;
+/// - the accelerometer, sampled once every five EEG samples, keeps its own rate and its raw
+///   values;
+/// - the markers of the samples become the events of the recording;
+/// - the timestamps start at the `StartDate_firstEEGTimestamp` of the header, or at the Unix
+///   epoch without one, and follow the sampling rate.
+pub fn read_nedf_from<R: Read>(mut reader: R, scale: Float) -> Result<Recording> {
+    let mut header = vec![0u8; HEADER_LENGTH];
+    reader.read_exact(&mut header)?;
+    let end = header.iter().position(|byte| *byte == 0).ok_or_else(|| {
+        EasyError::Header("the .nedf header is not terminated by a NUL byte".to_string())
+    })?;
+    let xml = std::str::from_utf8(&header[..end])
+        .map_err(|e| EasyError::Header(format!("the .nedf header is not UTF-8: {}", e)))?;
+    let (info, layout) = parse_header(xml)?;
+
+    let num_samples = layout.num_samples;
+    let num_channels = layout.num_channels;
+    let mut eeg = Vec::with_capacity(num_samples * num_channels);
+    let num_blocks = num_samples.div_ceil(SAMPLES_PER_BLOCK);
+    let mut acc = Vec::with_capacity(num_blocks * layout.num_acc_channels);
+    let mut markers = Vec::with_capacity(num_samples);
+
+    let stim_length = if layout.has_stim {
+        2 * num_channels * 3
+    } else {
+        0
+    };
+    let mut acc_bytes = vec![0u8; layout.num_acc_channels * 2];
+    let mut sample_bytes = vec![0u8; num_channels * 3 + stim_length + layout.marker_bytes];
+
+    // The last block holds the remaining samples
+    for first in (0..num_samples).step_by(SAMPLES_PER_BLOCK) {
+        reader.read_exact(&mut acc_bytes)?;
+        let block_acc: Vec<Float> = acc_bytes
+            .chunks(2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]) as Float)
+            .collect::<Vec<_>>();
+        acc.extend(block_acc);
+
+        for _ in first..num_samples.min(first + SAMPLES_PER_BLOCK) {
+            reader.read_exact(&mut sample_bytes)?;
+            eeg.extend(sample_bytes[..num_channels * 3].chunks(3).map(|bytes| {
+                // 24-bit big-endian two's complement
+                let value = i32::from_be_bytes([bytes[0], bytes[1], bytes[2], 0]) >> 8;
+                value as Float * NANOVOLTS_PER_STEP / scale
+            }));
+
+            let marker = &sample_bytes[num_channels * 3 + stim_length..];
+            markers.push(match marker {
+                [a, b, c, d] => i32::from_be_bytes([*a, *b, *c, *d]) as Float,
+                [byte] => *byte as Float,
+                _ => 0.0,
+            });
+        }
+    }
+
+    let start_date = info.device_info.start_date.unwrap_or(DateTime::UNIX_EPOCH);
+    let sampling_rate = info.eeg_settings.sampling_rate as Float;
+    let time: Vec<Float> = (0..num_samples)
+        .map(|i| i as Float / sampling_rate)
+        .collect();
+    let timestamps = time
+        .iter()
+        .map(|seconds| start_date + chrono::Duration::microseconds((seconds * 1e6).round() as i64))
+        .collect();
+
+    let parts = EasyParts {
+        electrodes: info.electrodes(),
+        start_date: Some(start_date),
+        eeg: Array2::from_shape_vec((num_samples, num_channels), eeg)?,
+        acc: None,
+        markers: Array2::from_shape_vec((num_samples, 1), markers)?,
+        time: Array2::from_shape_vec((num_samples, 1), time)?,
+        timestamps,
+    };
+    let mut recording =
+        Recording::from_parts(parts, info, sampling_rate, &scaled_units("nV", scale))?;
+    if layout.num_acc_channels > 0 {
+        recording.accelerometer = Some(Accelerometer {
+            channels: ["X", "Y", "Z"]
+                .iter()
+                .take(layout.num_acc_channels)
+                .map(|axis| Channel {
+                    name: axis.to_string(),
+                    unit: String::new(),
+                    kind: ChannelKind::Accelerometer,
+                })
+                .collect(),
+            sampling_rate: sampling_rate / SAMPLES_PER_BLOCK as Float,
+            data: Array2::from_shape_vec((num_blocks, layout.num_acc_channels), acc)?,
+        });
+    }
+    recording
+        .log
+        .push(format!("recording read from NEDF: {}", Utc::now()));
+    Ok(recording)
+}
+
+/// Maps the XML header onto the `.info` model and the layout of the binary data.
+fn parse_header(xml: &str) -> Result<(EEGData, Layout)> {
+    let document = roxmltree::Document::parse(xml)
+        .map_err(|e| EasyError::Header(format!("invalid .nedf XML: {}", e)))?;
+    let root = document.root_element();
+
+    if text(root, &["AdditionalChannelStatus"]).is_some_and(|status| status != "OFF") {
+        return Err(EasyError::Header(
+            "additional channels are not supported".to_string(),
+        ));
+    }
+
+    let eeg_settings = child(root, "EEGSettings")
+        .ok_or_else(|| EasyError::Header("no EEGSettings in the .nedf header".to_string()))?;
+    let number = |node: Node, path: &[&str]| -> Result<usize> {
+        let value = text(node, path).unwrap_or_default();
+        value.parse().map_err(|_| {
+            EasyError::Header(format!(
+                "invalid {} in the .nedf header: {:?}",
+                path.join("/"),
+                value
+            ))
+        })
+    };
+
+    let mut info = EEGData::new();
+    let version = text(root, &["NEDFversion"]).unwrap_or_default();
+    info.device_info.version = version.to_string();
+    info.device_info.device_class = text(root, &["StepDetails", "DeviceClass"])
+        .unwrap_or_default()
+        .to_string();
+    info.device_info.start_date = text(root, &["StepDetails", "StartDate_firstEEGTimestamp"])
+        .and_then(|millis| millis.parse().ok())
+        .and_then(DateTime::<Utc>::from_timestamp_millis);
+
+    let settings = &mut info.eeg_settings;
+    settings.total_channels = number(eeg_settings, &["TotalNumberOfChannels"])?;
+    settings.eeg_channels = settings.total_channels;
+    settings.records = number(eeg_settings, &["NumberOfRecordsOfEEG"])?;
+    settings.sampling_rate = number(eeg_settings, &["EEGSamplingRate"])? as f32;
+    if settings.sampling_rate <= 0.0 {
+        return Err(EasyError::Header(
+            "the .nedf header has no sampling rate".to_string(),
+        ));
+    }
+    settings.eeg_units = "nV".to_string();
+    if let Some(montage) = child(eeg_settings, "EEGMontage") {
+        for (i, channel) in montage.children().filter(Node::is_element).enumerate() {
+            settings
+                .montage
+                .insert(i + 1, channel.text().unwrap_or_default().trim().to_string());
+        }
+    }
+    if settings.montage.len() != settings.total_channels {
+        return Err(EasyError::Header(format!(
+            "{} channels declared, {} in the montage",
+            settings.total_channels,
+            settings.montage.len()
+        )));
+    }
+
+    let num_acc_channels = match text(root, &["NumberOfChannelsOfAccelerometer"]) {
+        Some(_) => number(root, &["NumberOfChannelsOfAccelerometer"])?,
+        None => 0,
+    };
+    if num_acc_channels > 3 {
+        return Err(EasyError::Header(format!(
+            "{} accelerometer channels declared, at most 3 are supported",
+            num_acc_channels
+        )));
+    }
+    if num_acc_channels > 0 {
+        settings.accelerometer = Some(AccelerometerData {
+            channels: num_acc_channels,
+            sampling_rate: settings.sampling_rate / SAMPLES_PER_BLOCK as f32,
+            units: String::new(),
+        });
+    }
+
+    let layout = Layout {
+        num_channels: settings.total_channels,
+        num_acc_channels,
+        num_samples: settings.records,
+        has_stim: child(root, "STIMSettings").is_some(),
+        marker_bytes: marker_bytes(version)?,
+    };
+    Ok((info, layout))
+}
+
+/// Returns the size of the marker of each sample for an `NEDFversion`, empty before 1.0.
+fn marker_bytes(version: &str) -> Result<usize> {
+    if version.is_empty() {
+        return Ok(1);
+    }
+    let invalid = || EasyError::Header(format!("invalid NEDFversion {:?}", version));
+    let mut numbers = version.split('.').map(|n| n.trim().parse::<u32>());
+    let major = numbers.next().ok_or_else(invalid)?.map_err(|_| invalid())?;
+    let minor = numbers.next().unwrap_or(Ok(0)).map_err(|_| invalid())?;
+    Ok(if (major, minor) >= (1, 3) { 4 } else { 1 })
+}
+
+/// Returns the first child element with the given tag name, ignoring case.
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children()
+        .find(|child| child.is_element() && child.tag_name().name().eq_ignore_ascii_case(name))
+}
+
+/// Returns the trimmed text of the element at `path` below `node`.
+fn text<'a>(node: Node<'a, '_>, path: &[&str]) -> Option<&'a str> {
+    path.iter()
+        .try_fold(node, |node, name| child(node, name))
+        .map(|node| node.text().unwrap_or_default().trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a `.nedf` file with 4 channels, accelerometer and stimulation settings.
+    fn sample_file(version: &str, num_samples: usize) -> Vec<u8> {
+        let xml = format!(
+            "<?xml version=\"1.0\"?>\n<nedf>\n\
+             <NEDFversion>{}</NEDFversion>\n\
+             <NumberOfChannelsOfAccelerometer>3</NumberOfChannelsOfAccelerometer>\n\
+             <stepDetails><DeviceClass>STARSTIM</DeviceClass>\
+             <StartDate_firstEEGTimestamp>1609459200000</StartDate_firstEEGTimestamp></stepDetails>\n\
+             <EEGSettings>\n<TotalNumberOfChannels>4</TotalNumberOfChannels>\n\
+             <EEGSamplingRate>500</EEGSamplingRate>\n\
+             <EEGMontage><C>Fp1</C><C>Fp2</C><C>O1</C><C>O2</C></EEGMontage>\n\
+             <NumberOfRecordsOfEEG>{}</NumberOfRecordsOfEEG>\n</EEGSettings>\n\
+             <STIMSettings/>\n</nedf>\n",
+            version, num_samples
+        );
+        let mut bytes = xml.into_bytes();
+        bytes.resize(HEADER_LENGTH, 0);
+
+        for first in (0..num_samples).step_by(SAMPLES_PER_BLOCK) {
+            for axis in 0..3u16 {
+                bytes.extend_from_slice(&(first as u16 * 10 + axis).to_be_bytes());
+            }
+            for i in first..num_samples.min(first + SAMPLES_PER_BLOCK) {
+                for channel in 0..4i32 {
+                    let value = (i as i32 - 3) * 100 + channel;
+                    bytes.extend_from_slice(&value.to_be_bytes()[1..]);
+                }
+                bytes.extend(std::iter::repeat_n(0xAA, 2 * 4 * 3));
+                let marker: i32 = if i == 6 { 9 } else { 0 };
+                let marker_bytes = marker_bytes(version).unwrap();
+                bytes.extend_from_slice(&marker.to_be_bytes()[4 - marker_bytes..]);
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_read_nedf() {
+        let recording = read_nedf_from(sample_file("1.4", 12).as_slice(), 1.0).unwrap();
+
+        assert_eq!(recording.device().device_class, "STARSTIM");
+        assert_eq!(recording.sampling_rate, 500.0);
+        assert_eq!(recording.channel_names(), vec!["Fp1", "Fp2", "O1", "O2"]);
+        assert_eq!(recording.eeg_unit(), "nV");
+
+        assert_eq!(recording.eeg.dim(), (12, 4));
+        assert_eq!(recording.eeg[[0, 0]], -300.0 * NANOVOLTS_PER_STEP);
+        assert_eq!(recording.eeg[[11, 3]], 803.0 * NANOVOLTS_PER_STEP);
+
+        let accelerometer = recording.accelerometer.as_ref().unwrap();
+        assert_eq!(accelerometer.sampling_rate, 100.0);
+        assert_eq!(accelerometer.data.dim(), (3, 3));
+        let acc = recording.accelerometer_at_eeg_rate().unwrap();
+        assert_eq!(acc.row(4).to_vec(), vec![0.0, 1.0, 2.0]);
+        assert_eq!(acc.row(5).to_vec(), vec![50.0, 51.0, 52.0]);
+        assert_eq!(acc.row(11).to_vec(), vec![100.0, 101.0, 102.0]);
+
+        assert_eq!(recording.events.len(), 1);
+        assert_eq!(recording.events[0].sample_index, 6);
+        assert_eq!(recording.events[0].code, 9.0);
+        assert_eq!(recording.time()[5], 0.01);
+        assert_eq!(recording.timestamps[5].timestamp_millis(), 1609459200010);
+    }
+
+    #[test]
+    fn test_nedf_marker_bytes() {
+        assert_eq!(marker_bytes("").unwrap(), 1);
+        assert_eq!(marker_bytes("1.2").unwrap(), 1);
+        assert_eq!(marker_bytes("1.3").unwrap(), 4);
+        assert_eq!(marker_bytes("2").unwrap(), 4);
+        assert!(marker_bytes("draft").is_err());
+
+        // Single-byte markers keep the following samples aligned
+        let recording = read_nedf_from(sample_file("1.2", 12).as_slice(), 1.0).unwrap();
+        assert_eq!(recording.eeg[[11, 3]], 803.0 * NANOVOLTS_PER_STEP);
+        assert_eq!(recording.events.len(), 1);
+        assert_eq!(recording.events[0].sample_index, 6);
+        assert_eq!(recording.events[0].code, 9.0);
+    }
+
+    #[test]
+    fn test_read_truncated_nedf() {
+        let mut bytes = sample_file("1.4", 12);
+        bytes.truncate(bytes.len() - 10);
+        assert!(matches!(
+            read_nedf_from(bytes.as_slice(), 1.0),
+            Err(EasyError::Io(_))
+        ));
+
+        let mut bytes = sample_file("1.4", 12);
+        bytes[..HEADER_LENGTH].fill(b' ');
+        assert!(matches!(
+            read_nedf_from(bytes.as_slice(), 1.0),
+            Err(EasyError::Header(_))
+        ));
+
+        // Accelerometer axes are X, Y and Z
+        let xml = String::from_utf8(sample_file("1.4", 12)[..HEADER_LENGTH].to_vec()).unwrap();
+        let mut bytes = xml
+            .replace(
+                "<NumberOfChannelsOfAccelerometer>3",
+                "<NumberOfChannelsOfAccelerometer>4",
+            )
+            .into_bytes();
+        bytes.resize(HEADER_LENGTH, 0);
+        assert!(matches!(
+            read_nedf_from(bytes.as_slice(), 1.0),
+            Err(EasyError::Header(_))
+        ));
+    }
+}
@@ -0,0 +1,291 @@
+use crate::easy_reader::Float;
+use crate::error::{EasyError, Result};
+use crate::recording::Recording;
+use chrono::{DateTime, Utc};
+use flate2::Crc;
+use ndarray::{ArrayView2, Axis};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// Magic string starting every `.npy` file, followed by format version 1.0.
+const NPY_MAGIC: &[u8] = b"\x93NUMPY\x01\x00";
+
+/// `.npy` headers are padded so that the data starts on a multiple of this size.
+const NPY_ALIGNMENT: usize = 64;
+
+/// Writes a 2-D array as a `.npy` file of little-endian doubles.
+pub fn write_npy(array: ArrayView2<Float>, filename: &str) -> Result<()> {
+    let mut file = BufWriter::new(File::create(filename)?);
+    write_npy_to(array, &mut file)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Writes a 2-D array in the `.npy` format to any writer.
+pub fn write_npy_to<W: Write>(array: ArrayView2<Float>, writer: &mut W) -> Result<()> {
+    writer.write_all(&npy_floats(array))?;
+    Ok(())
+}
+
+/// Writes a recording as a NumPy `.npz` archive.
+///
+/// See [`write_npz_to`] for the arrays it holds.
+pub fn write_npz(recording: &Recording, filename: &str) -> Result<()> {
+    let mut file = BufWriter::new(File::create(filename)?);
+    write_npz_to(recording, &mut file)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Writes a recording in the NumPy `.npz` format to any writer.
+///
+/// The archive holds the arrays of NIC's Python `NEClass` under the same names: `np_eeg`,
+/// `np_acc` (only when the recording has an accelerometer, at the EEG rate), `np_markers`
+/// and `np_time`, as `float64`; `electrodes`, a unicode array of the channel names; and
+/// `start_date`, a `datetime64[ms]` scalar. None of them needs `allow_pickle` to be loaded:
+///
+/// ```python
+/// data = numpy.load("Example01.npz")
+/// eeg = data["np_eeg"]
+/// ```
+pub fn write_npz_to<W: Write>(recording: &Recording, writer: &mut W) -> Result<()> {
+    let mut entries = vec![("np_eeg", npy_floats(recording.eeg.view()))];
+    if let Some(acc) = recording.accelerometer_at_eeg_rate() {
+        entries.push(("np_acc", npy_floats(acc.view())));
+    }
+    let column = |values: ndarray::Array1<Float>| npy_floats(values.insert_axis(Axis(1)).view());
+    entries.push(("np_markers", column(recording.marker_column())));
+    entries.push(("np_time", column(recording.time())));
+    entries.push(("electrodes", npy_strings(&recording.channel_names())));
+    entries.push(("start_date", npy_datetime(recording.start_time)));
+
+    write_zip(
+        writer,
+        entries
+            .iter()
+            .map(|(name, bytes)| (format!("{}.npy", name), bytes.as_slice())),
+    )
+}
+
+/// Encodes the header of a `.npy` file.
+fn npy_header(descr: &str, shape: &[usize]) -> Vec<u8> {
+    let shape = match shape {
+        [length] => format!("({},)", length),
+        _ => format!(
+            "({})",
+            shape
+                .iter()
+                .map(|length| length.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let mut dict = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        descr, shape
+    );
+
+    // Magic, version and header length take 10 bytes, the header ends with a newline
+    let unpadded = NPY_MAGIC.len() + 2 + dict.len() + 1;
+    let padding = (NPY_ALIGNMENT - unpadded % NPY_ALIGNMENT) % NPY_ALIGNMENT;
+    dict.push_str(&" ".repeat(padding));
+    dict.push('\n');
+
+    let mut header = NPY_MAGIC.to_vec();
+    header.extend_from_slice(&(dict.len() as u16).to_le_bytes());
+    header.extend_from_slice(dict.as_bytes());
+    header
+}
+
+/// Encodes a 2-D array of doubles, in row-major order.
+fn npy_floats(array: ArrayView2<Float>) -> Vec<u8> {
+    let mut bytes = npy_header("<f8", array.shape());
+    bytes.reserve(array.len() * 8);
+    for value in array.iter() {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes
+}
+
+/// Encodes strings as a 1-D array of fixed-width UTF-32 strings.
+fn npy_strings(strings: &[String]) -> Vec<u8> {
+    let width = strings
+        .iter()
+        .map(|string| string.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    let mut bytes = npy_header(&format!("<U{}", width), &[strings.len()]);
+    for string in strings {
+        let mut chars = 0;
+        for c in string.chars() {
+            bytes.extend_from_slice(&(c as u32).to_le_bytes());
+            chars += 1;
+        }
+        bytes.resize(bytes.len() + (width - chars) * 4, 0);
+    }
+    bytes
+}
+
+/// Encodes a date as a `datetime64[ms]` scalar.
+fn npy_datetime(date: DateTime<Utc>) -> Vec<u8> {
+    let mut bytes = npy_header("<M8[ms]", &[]);
+    bytes.extend_from_slice(&date.timestamp_millis().to_le_bytes());
+    bytes
+}
+
+/// Writes files into an uncompressed zip archive, as `numpy.savez` does.
+fn write_zip<'a, W: Write>(
+    writer: &mut W,
+    files: impl Iterator<Item = (String, &'a [u8])>,
+) -> Result<()> {
+    let too_large = || EasyError::Unsupported("the .npz archive would exceed 4 GiB".to_string());
+
+    let mut central_directory = Vec::new();
+    let mut offset: u64 = 0;
+    let mut num_files: u16 = 0;
+    for (name, data) in files {
+        let mut crc = Crc::new();
+        crc.update(data);
+        let size = u32::try_from(data.len()).map_err(|_| too_large())?;
+        let local_offset = u32::try_from(offset).map_err(|_| too_large())?;
+
+        // Version needed, flags, method (stored), time, date, CRC and sizes
+        let mut common = Vec::with_capacity(26);
+        common.extend_from_slice(&20u16.to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+        common.extend_from_slice(&0x21u16.to_le_bytes());
+        common.extend_from_slice(&crc.sum().to_le_bytes());
+        common.extend_from_slice(&size.to_le_bytes());
+        common.extend_from_slice(&size.to_le_bytes());
+        common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+
+        let mut local_header = 0x04034b50u32.to_le_bytes().to_vec();
+        local_header.extend_from_slice(&common);
+        local_header.extend_from_slice(&0u16.to_le_bytes());
+        local_header.extend_from_slice(name.as_bytes());
+        writer.write_all(&local_header)?;
+        writer.write_all(data)?;
+
+        // Version made by, the common fields, then comment, disk, attributes and offset
+        central_directory.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        central_directory.extend_from_slice(&20u16.to_le_bytes());
+        central_directory.extend_from_slice(&common);
+        central_directory.extend_from_slice(&[0; 12]);
+        central_directory.extend_from_slice(&local_offset.to_le_bytes());
+        central_directory.extend_from_slice(name.as_bytes());
+
+        offset += (local_header.len() + data.len()) as u64;
+        num_files += 1;
+    }
+
+    let directory_offset = u32::try_from(offset).map_err(|_| too_large())?;
+    writer.write_all(&central_directory)?;
+
+    let mut end = 0x06054b50u32.to_le_bytes().to_vec();
+    end.extend_from_slice(&[0; 4]);
+    end.extend_from_slice(&num_files.to_le_bytes());
+    end.extend_from_slice(&num_files.to_le_bytes());
+    end.extend_from_slice(&(central_directory.len() as u32).to_le_bytes());
+    end.extend_from_slice(&directory_offset.to_le_bytes());
+    end.extend_from_slice(&0u16.to_le_bytes());
+    writer.write_all(&end)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::SampleRecording;
+    use ndarray::array;
+
+    /// Returns the name and content of the files of a stored zip archive.
+    fn unzip(bytes: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]) as usize;
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap()) as usize;
+
+        let end = bytes.len() - 22;
+        assert_eq!(u32_at(end), 0x06054b50);
+        let mut entry = u32_at(end + 16);
+        (0..u16_at(end + 10))
+            .map(|_| {
+                assert_eq!(u32_at(entry), 0x02014b50);
+                let name_length = u16_at(entry + 28);
+                let name = String::from_utf8(bytes[entry + 46..entry + 46 + name_length].to_vec());
+                let local = u32_at(entry + 42);
+                let size = u32_at(entry + 20);
+                let start = local + 30 + u16_at(local + 26);
+
+                let mut crc = Crc::new();
+                crc.update(&bytes[start..start + size]);
+                assert_eq!(crc.sum() as usize, u32_at(entry + 16));
+
+                entry += 46 + name_length;
+                (name.unwrap(), bytes[start..start + size].to_vec())
+            })
+            .collect()
+    }
+
+    /// Splits a `.npy` file into its header and its data.
+    fn split_npy(bytes: &[u8]) -> (&str, &[u8]) {
+        let header_length = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        let header = std::str::from_utf8(&bytes[10..10 + header_length]).unwrap();
+        (header, &bytes[10 + header_length..])
+    }
+
+    #[test]
+    fn test_npy_header() {
+        let bytes = npy_floats(array![[1.0, 2.0], [3.0, 4.0], [5.0, 6.0]].view());
+        assert_eq!(&bytes[..8], NPY_MAGIC);
+        let (header, data) = split_npy(&bytes);
+        assert_eq!((10 + header.len()) % NPY_ALIGNMENT, 0);
+        assert!(header.starts_with("{'descr': '<f8', 'fortran_order': False, 'shape': (3, 2), }"));
+        assert!(header.ends_with(" \n"));
+        assert_eq!(data.len(), 6 * 8);
+        assert_eq!(&data[8..16], &2.0f64.to_le_bytes());
+
+        let strings = npy_strings(&["Fp1".to_string(), "µV".to_string()]);
+        let (header, data) = split_npy(&strings);
+        assert!(header.contains("'descr': '<U3'"));
+        assert!(header.contains("'shape': (2,)"));
+        assert_eq!(data.len(), 2 * 3 * 4);
+        assert_eq!(&data[12..16], &('µ' as u32).to_le_bytes());
+    }
+
+    #[test]
+    fn test_write_npz() {
+        let reader = SampleRecording::new(500.0, 5)
+            .eeg(|i, c| if c == 0 { i as i64 } else { c as i64 })
+            .accelerometer(|_| [10, 20, 30])
+            .scale(1.0)
+            .without_info()
+            .reader();
+
+        let recording = reader.recording().unwrap();
+        let mut bytes = Vec::new();
+        write_npz_to(&recording, &mut bytes).unwrap();
+        let files = unzip(&bytes);
+        let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "np_eeg.npy",
+                "np_acc.npy",
+                "np_markers.npy",
+                "np_time.npy",
+                "electrodes.npy",
+                "start_date.npy"
+            ]
+        );
+        assert_eq!(files[0].1, npy_floats(reader.eeg().unwrap()));
+        assert_eq!(files[3].1, npy_floats(reader.time().unwrap()));
+
+        let (header, data) = split_npy(&files[5].1);
+        assert!(header.contains("'descr': '<M8[ms]'"));
+        assert!(header.contains("'shape': ()"));
+        assert_eq!(data, &1609459200000i64.to_le_bytes());
+    }
+}
@@ -0,0 +1,425 @@
+use crate::easy_reader::{shift_prefix, EasyParts, Float};
+use crate::error::{EasyError, Result};
+use crate::info::{AccelerometerData, DeviceInfo, EEGData};
+use chrono::{DateTime, Utc};
+use ndarray::{Array1, Array2, ArrayView1};
+use std::collections::HashMap;
+
+/// Kind of signal recorded by a channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelKind {
+    Eeg,
+    Accelerometer,
+}
+
+/// A recorded signal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Channel {
+    /// Name of the electrode or axis, e.g. `Fp1` or `X`.
+    pub name: String,
+    /// Unit of the values, e.g. `uV`.
+    pub unit: String,
+    pub kind: ChannelKind,
+}
+
+/// Accelerometer signals, sampled at their own rate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Accelerometer {
+    /// The X, Y and Z axes.
+    pub channels: Vec<Channel>,
+    pub sampling_rate: Float,
+    /// Values, shape `(num_samples, 3)`.
+    pub data: Array2<Float>,
+}
+
+/// A marker, held over one or more consecutive samples.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    /// Index of the first sample of the event.
+    pub sample_index: usize,
+    /// Number of consecutive samples holding the marker.
+    pub duration: usize,
+    /// Time of the first sample.
+    pub timestamp: DateTime<Utc>,
+    /// Time of the first sample in seconds relative to the start of the recording.
+    pub onset_seconds: Float,
+    /// Marker code, as stored in the `.easy` file.
+    pub code: Float,
+    /// Description of the code in the trigger table of the `.info` file.
+    pub description: Option<String>,
+}
+
+impl Event {
+    /// Returns the description of the event, or its code when it has none.
+    pub fn label(&self) -> String {
+        self.description
+            .clone()
+            .unwrap_or_else(|| format!("{}", self.code))
+    }
+}
+
+/// A recording, whatever the format it was read from.
+///
+/// Readers produce it ([`EasyReader::recording`](crate::easy_reader::EasyReader::recording),
+/// [`edf::read_edf`](crate::edf::read_edf), [`nedf::read_nedf`](crate::nedf::read_nedf)) and
+/// exporters and processing functions consume it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    /// The EEG channels, in column order.
+    pub channels: Vec<Channel>,
+    /// EEG sampling rate, in Hz.
+    pub sampling_rate: Float,
+    /// Time of the first sample.
+    pub start_time: DateTime<Utc>,
+    /// EEG data, shape `(num_samples, num_channels)`.
+    pub eeg: Array2<Float>,
+    /// Absolute time of each EEG sample.
+    pub timestamps: Vec<DateTime<Utc>>,
+    pub accelerometer: Option<Accelerometer>,
+    /// The markers, in order.
+    pub events: Vec<Event>,
+    /// Metadata of the source: device information, settings and trigger table.
+    pub info: EEGData,
+    /// Path of the file the recording was read from, empty when it was read from memory.
+    pub source: String,
+    /// Steps the recording went through, from the reading of its source on.
+    pub log: Vec<String>,
+}
+
+impl Recording {
+    /// Assembles a recording from the arrays of a reader.
+    ///
+    /// The EEG is in `eeg_unit`. The accelerometer, if any, has one sample per EEG sample and
+    /// the unit declared in `info`. Runs of identical non-zero markers become events, described
+    /// by the trigger table of `info`.
+    pub fn from_parts(
+        parts: EasyParts,
+        info: EEGData,
+        sampling_rate: Float,
+        eeg_unit: &str,
+    ) -> Result<Self> {
+        let start_time = parts
+            .start_date
+            .or_else(|| parts.timestamps.first().copied())
+            .ok_or(EasyError::EmptyFile)?;
+        if parts.eeg.ncols() != parts.electrodes.len() {
+            return Err(EasyError::InfoMismatch(format!(
+                "{} electrode names for {} EEG channels",
+                parts.electrodes.len(),
+                parts.eeg.ncols()
+            )));
+        }
+
+        let channels = parts
+            .electrodes
+            .into_iter()
+            .map(|name| Channel {
+                name,
+                unit: eeg_unit.to_string(),
+                kind: ChannelKind::Eeg,
+            })
+            .collect();
+
+        let acc_unit = info
+            .eeg_settings
+            .accelerometer
+            .as_ref()
+            .map(|acc| acc.units.clone())
+            .unwrap_or_default();
+        let accelerometer = parts.acc.map(|data| Accelerometer {
+            channels: ["X", "Y", "Z"]
+                .iter()
+                .map(|axis| Channel {
+                    name: axis.to_string(),
+                    unit: acc_unit.clone(),
+                    kind: ChannelKind::Accelerometer,
+                })
+                .collect(),
+            sampling_rate,
+            data,
+        });
+
+        let events = events_from_markers(
+            parts.markers.column(0),
+            &parts.timestamps,
+            start_time,
+            &info.trigger_info.triggers,
+        );
+
+        Ok(Recording {
+            channels,
+            sampling_rate,
+            start_time,
+            eeg: parts.eeg,
+            timestamps: parts.timestamps,
+            accelerometer,
+            events,
+            info,
+            source: String::new(),
+            log: Vec::new(),
+        })
+    }
+
+    /// Number of EEG samples.
+    pub fn num_samples(&self) -> usize {
+        self.eeg.nrows()
+    }
+
+    /// Number of EEG channels.
+    pub fn num_channels(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Duration of the recording in seconds, from the number of samples and the sampling rate.
+    pub fn duration(&self) -> Float {
+        self.num_samples() as Float / self.sampling_rate
+    }
+
+    /// Names of the EEG channels, in column order.
+    pub fn channel_names(&self) -> Vec<String> {
+        self.channels
+            .iter()
+            .map(|channel| channel.name.clone())
+            .collect()
+    }
+
+    /// Unit of the EEG, the unit of the first channel.
+    pub fn eeg_unit(&self) -> &str {
+        self.channels
+            .first()
+            .map_or("", |channel| channel.unit.as_str())
+    }
+
+    /// Device information of the source.
+    pub fn device(&self) -> &DeviceInfo {
+        &self.info.device_info
+    }
+
+    /// Time of each EEG sample in seconds relative to the start of the recording.
+    pub fn time(&self) -> Array1<Float> {
+        self.timestamps
+            .iter()
+            .map(|timestamp| (*timestamp - self.start_time).num_milliseconds() as Float / 1000.0)
+            .collect()
+    }
+
+    /// Rebuilds the marker of each EEG sample from the events, `0` where there is none.
+    pub fn marker_column(&self) -> Array1<Float> {
+        let mut markers = Array1::zeros(self.num_samples());
+        for event in &self.events {
+            let end = (event.sample_index + event.duration).min(markers.len());
+            for marker in markers.iter_mut().take(end).skip(event.sample_index) {
+                *marker = event.code;
+            }
+        }
+        markers
+    }
+
+    /// Returns the accelerometer with one sample per EEG sample, each value held until the next.
+    pub fn accelerometer_at_eeg_rate(&self) -> Option<Array2<Float>> {
+        let accelerometer = self.accelerometer.as_ref()?;
+        if accelerometer.sampling_rate == self.sampling_rate
+            && accelerometer.data.nrows() == self.num_samples()
+        {
+            return Some(accelerometer.data.clone());
+        }
+
+        let ratio = accelerometer.sampling_rate / self.sampling_rate;
+        let last = accelerometer.data.nrows().checked_sub(1)?;
+        Some(Array2::from_shape_fn(
+            (self.num_samples(), 3),
+            |(i, axis)| {
+                let row = ((i as Float * ratio) as usize).min(last);
+                accelerometer.data[[row, axis]]
+            },
+        ))
+    }
+
+    /// Returns the `.info` metadata describing the recording.
+    ///
+    /// The channel count, montage, number of records, sampling rate, accelerometer and trigger
+    /// table are taken from the recording, the rest from [`info`](Self::info). The EEG units
+    /// are those of the values stored in a `.easy` file written with `scale`, which multiplies
+    /// the values of the recording.
+    pub fn to_info(&self, scale: Float) -> EEGData {
+        let mut info = self.info.clone();
+        info.device_info.start_date = Some(self.start_time);
+
+        let settings = &mut info.eeg_settings;
+        settings.eeg_channels = self.num_channels();
+        settings.total_channels = settings.total_channels.max(self.num_channels());
+        settings.records = self.num_samples();
+        settings.sampling_rate = self.sampling_rate as f32;
+        settings.eeg_units = stored_units(self.eeg_unit(), scale);
+        settings.montage = self
+            .channels
+            .iter()
+            .enumerate()
+            .map(|(i, channel)| (i + 1, channel.name.clone()))
+            .collect();
+        settings.accelerometer =
+            self.accelerometer
+                .as_ref()
+                .map(|accelerometer| AccelerometerData {
+                    channels: accelerometer.channels.len(),
+                    sampling_rate: accelerometer.sampling_rate as f32,
+                    units: accelerometer
+                        .channels
+                        .first()
+                        .map(|channel| channel.unit.clone())
+                        .unwrap_or_default(),
+                });
+
+        for event in &self.events {
+            if let Some(description) = &event.description {
+                info.trigger_info
+                    .triggers
+                    .insert(event.code as u32, description.clone());
+            }
+        }
+        info
+    }
+}
+
+/// Collapses a marker column into events, one per run of identical non-zero codes.
+pub(crate) fn events_from_markers(
+    markers: ArrayView1<Float>,
+    timestamps: &[DateTime<Utc>],
+    start_time: DateTime<Utc>,
+    triggers: &HashMap<u32, String>,
+) -> Vec<Event> {
+    let mut detector = EventDetector::default();
+    let mut events = Vec::new();
+    for (i, code) in markers.iter().enumerate() {
+        let timestamp = timestamps.get(i).copied().unwrap_or(start_time);
+        detector.push(*code, timestamp, start_time, triggers, &mut events);
+    }
+    events
+}
+
+/// Turns markers into events one sample at a time, so that runs spanning several chunks of a
+/// stream are detected once.
+#[derive(Debug, Default)]
+pub(crate) struct EventDetector {
+    /// Index of the next sample in the recording.
+    sample_index: usize,
+    /// Marker of the previous sample.
+    previous: Float,
+}
+
+impl EventDetector {
+    /// Handles the marker of the next sample.
+    ///
+    /// A new event is appended to `events` when a run starts, and the last event of `events`
+    /// is extended while the run goes on. A run that started before `events` was emptied is
+    /// not reported again.
+    pub(crate) fn push(
+        &mut self,
+        code: Float,
+        timestamp: DateTime<Utc>,
+        start_time: DateTime<Utc>,
+        triggers: &HashMap<u32, String>,
+        events: &mut Vec<Event>,
+    ) {
+        if code != 0.0 {
+            if code != self.previous {
+                events.push(Event {
+                    sample_index: self.sample_index,
+                    duration: 1,
+                    timestamp,
+                    onset_seconds: (timestamp - start_time).num_milliseconds() as Float / 1000.0,
+                    code,
+                    description: triggers.get(&(code as u32)).cloned(),
+                });
+            } else if let Some(event) = events.last_mut() {
+                event.duration += 1;
+            }
+        }
+        self.previous = code;
+        self.sample_index += 1;
+    }
+}
+
+/// Returns the unit of the values stored in a `.easy` file, which are multiplied by `scale`.
+///
+/// This is the inverse of the conversion done when reading: `uV` values written with a scale
+/// of `1000` are stored in `nV`.
+fn stored_units(units: &str, scale: Float) -> String {
+    if scale == 1.0 {
+        return units.to_string();
+    }
+    shift_prefix(units, 1.0 / scale).unwrap_or_else(|| format!("{units} * {scale}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::SampleRecording;
+
+    fn sample_recording() -> Recording {
+        SampleRecording::new(500.0, 10)
+            .eeg(|i, c| {
+                if c == 0 {
+                    i as i64 * 1000
+                } else {
+                    c as i64 * 1000
+                }
+            })
+            .accelerometer(|_| [1, 2, 3])
+            .markers(&[(2, 3), (3, 3), (4, 3), (7, 5)])
+            .settings("EEG units: nV\nAccelerometer data: ON\n")
+            .triggers(&[(3, "Stimulus")])
+            .recording()
+    }
+
+    #[test]
+    fn test_recording() {
+        let recording = sample_recording();
+        assert_eq!(recording.num_samples(), 10);
+        assert_eq!(recording.num_channels(), 8);
+        assert_eq!(recording.channels[0].name, "Ch1");
+        assert_eq!(recording.eeg_unit(), "uV");
+        assert_eq!(recording.sampling_rate, 500.0);
+        assert_eq!(recording.duration(), 0.02);
+        assert_eq!(recording.time()[3], 0.006);
+
+        assert_eq!(recording.events.len(), 2);
+        let event = &recording.events[0];
+        assert_eq!((event.sample_index, event.duration), (2, 3));
+        assert_eq!(event.onset_seconds, 0.004);
+        assert_eq!(event.label(), "Stimulus");
+        assert_eq!(recording.events[1].label(), "5");
+        assert_eq!(
+            recording.marker_column().to_vec(),
+            vec![0.0, 0.0, 3.0, 3.0, 3.0, 0.0, 0.0, 5.0, 0.0, 0.0]
+        );
+
+        let info = recording.to_info(1000.0);
+        assert_eq!(info.eeg_settings.eeg_units, "nV");
+        assert_eq!(info.eeg_settings.records, 10);
+        assert_eq!(info.eeg_settings.montage[&8], "Ch8");
+        assert_eq!(info.trigger_info.triggers[&3], "Stimulus");
+    }
+
+    #[test]
+    fn test_accelerometer_at_eeg_rate() {
+        let mut recording = sample_recording();
+        let accelerometer = recording.accelerometer.as_mut().unwrap();
+        accelerometer.sampling_rate = 100.0;
+        accelerometer.data = Array2::from_shape_fn((2, 3), |(i, axis)| (i * 10 + axis) as Float);
+
+        let acc = recording.accelerometer_at_eeg_rate().unwrap();
+        assert_eq!(acc.dim(), (10, 3));
+        assert_eq!(acc.row(4).to_vec(), vec![0.0, 1.0, 2.0]);
+        assert_eq!(acc.row(5).to_vec(), vec![10.0, 11.0, 12.0]);
+        assert_eq!(acc.row(9).to_vec(), vec![10.0, 11.0, 12.0]);
+    }
+
+    #[test]
+    fn test_stored_units() {
+        assert_eq!(stored_units("uV", 1000.0), "nV");
+        assert_eq!(stored_units("nV", 1.0), "nV");
+        assert_eq!(stored_units("mV", 0.001), "V");
+        assert_eq!(stored_units("counts", 2.0), "counts * 2");
+    }
+}
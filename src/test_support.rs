@@ -0,0 +1,186 @@
+use crate::easy_reader::{EasyReader, Float};
+use crate::recording::Recording;
+
+/// First timestamp of the sample files, 2021-01-01 00:00:00 UTC in milliseconds.
+pub(crate) const START_MS: u64 = 1609459200000;
+
+/// Channel names of the sample montage.
+pub(crate) const MONTAGE: [&str; 8] = ["Fp1", "Fp2", "F3", "F4", "C3", "C4", "O1", "O2"];
+
+/// Builds a `.easy` file with 8 channels and the matching `.info` file.
+///
+/// Samples are evenly spaced at the sampling rate from [`START_MS`], up to the gaps added with
+/// [`gap`](Self::gap). The EEG is 0 and there are no markers unless set.
+pub(crate) struct SampleRecording {
+    rate: Float,
+    num_samples: usize,
+    start_ms: u64,
+    scale: Float,
+    eeg: Box<dyn Fn(usize, usize) -> i64>,
+    acc: Option<Box<dyn Fn(usize) -> [i64; 3]>>,
+    markers: Vec<(usize, u32)>,
+    gaps: Vec<(usize, u64)>,
+    montage: Vec<String>,
+    triggers: Vec<(u32, String)>,
+    step_details: String,
+    settings: String,
+    info: bool,
+}
+
+impl SampleRecording {
+    /// `num_samples` samples at `rate` Hz, read with a scale of `1000`.
+    pub(crate) fn new(rate: Float, num_samples: usize) -> Self {
+        SampleRecording {
+            rate,
+            num_samples,
+            start_ms: START_MS,
+            scale: 1000.0,
+            eeg: Box::new(|_, _| 0),
+            acc: None,
+            markers: Vec::new(),
+            gaps: Vec::new(),
+            montage: Vec::new(),
+            triggers: Vec::new(),
+            step_details: String::new(),
+            settings: String::new(),
+            info: true,
+        }
+    }
+
+    /// Stored value of each sample and channel.
+    pub(crate) fn eeg(mut self, eeg: impl Fn(usize, usize) -> i64 + 'static) -> Self {
+        self.eeg = Box::new(eeg);
+        self
+    }
+
+    /// Adds accelerometer columns with the X, Y and Z values of each sample.
+    pub(crate) fn accelerometer(mut self, acc: impl Fn(usize) -> [i64; 3] + 'static) -> Self {
+        self.acc = Some(Box::new(acc));
+        self
+    }
+
+    /// Sets the marker of some samples.
+    pub(crate) fn markers(mut self, markers: &[(usize, u32)]) -> Self {
+        self.markers.extend_from_slice(markers);
+        self
+    }
+
+    /// Delays the samples after `sample` by `ms` milliseconds.
+    pub(crate) fn gap(mut self, sample: usize, ms: u64) -> Self {
+        self.gaps.push((sample, ms));
+        self
+    }
+
+    pub(crate) fn start_ms(mut self, start_ms: u64) -> Self {
+        self.start_ms = start_ms;
+        self
+    }
+
+    pub(crate) fn scale(mut self, scale: Float) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Names the channels in the `.info` file.
+    pub(crate) fn montage(mut self, names: &[&str]) -> Self {
+        self.montage = names.iter().map(|name| name.to_string()).collect();
+        self
+    }
+
+    /// Adds a trigger table to the `.info` file.
+    pub(crate) fn triggers(mut self, triggers: &[(u32, &str)]) -> Self {
+        self.triggers = triggers
+            .iter()
+            .map(|(code, description)| (*code, description.to_string()))
+            .collect();
+        self
+    }
+
+    /// Adds lines to the `Step Details` section of the `.info` file.
+    pub(crate) fn step_details(mut self, lines: &str) -> Self {
+        self.step_details.push_str(lines);
+        self
+    }
+
+    /// Adds lines to the `EEG Settings` section of the `.info` file.
+    pub(crate) fn settings(mut self, lines: &str) -> Self {
+        self.settings.push_str(lines);
+        self
+    }
+
+    /// Reads the data without `.info` file.
+    pub(crate) fn without_info(mut self) -> Self {
+        self.info = false;
+        self
+    }
+
+    /// Content of the `.easy` file.
+    pub(crate) fn content(&self) -> String {
+        let mut content = String::new();
+        for i in 0..self.num_samples {
+            let mut columns: Vec<String> = (0..8).map(|c| (self.eeg)(i, c).to_string()).collect();
+            if let Some(acc) = &self.acc {
+                columns.extend(acc(i).iter().map(|value| value.to_string()));
+            }
+            let marker = self
+                .markers
+                .iter()
+                .rfind(|(sample, _)| *sample == i)
+                .map_or(0, |(_, code)| *code);
+            let delay: u64 = self
+                .gaps
+                .iter()
+                .filter(|(sample, _)| i > *sample)
+                .map(|(_, ms)| ms)
+                .sum();
+            let timestamp =
+                self.start_ms + (i as Float * 1000.0 / self.rate).round() as u64 + delay;
+            content.push_str(&format!(
+                "{}\t{}\t{}\n",
+                columns.join("\t"),
+                marker,
+                timestamp
+            ));
+        }
+        content
+    }
+
+    /// Content of the `.info` file.
+    pub(crate) fn info(&self) -> String {
+        let mut info = String::new();
+        if !self.step_details.is_empty() {
+            info.push_str(&format!("Step Details\n{}\n", self.step_details));
+        }
+        info.push_str(&format!(
+            "EEG Settings\nEEG sampling rate: {} Samples/second\n{}",
+            self.rate, self.settings
+        ));
+        for (i, name) in self.montage.iter().enumerate() {
+            info.push_str(&format!("Channel {}: {}\n", i + 1, name));
+        }
+        if !self.triggers.is_empty() {
+            info.push_str("Trigger information\nCode Description\n");
+            for (code, description) in &self.triggers {
+                info.push_str(&format!("{} {}\n", code, description));
+            }
+        }
+        info
+    }
+
+    /// Returns a reader over the files, without loading the data.
+    pub(crate) fn open(&self) -> EasyReader {
+        let info = self.info.then(|| self.info());
+        EasyReader::from_bytes(self.content(), info.as_deref(), self.scale, false).unwrap()
+    }
+
+    /// Returns a reader over the files, with the data loaded.
+    pub(crate) fn reader(&self) -> EasyReader {
+        let mut reader = self.open();
+        reader.parse_data().unwrap();
+        reader
+    }
+
+    pub(crate) fn recording(&self) -> Recording {
+        self.reader().into_recording().unwrap()
+    }
+}
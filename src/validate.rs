@@ -0,0 +1,309 @@
+use crate::easy_reader::{EasyReader, Float};
+use chrono::{DateTime, Utc};
+use std::fmt;
+
+/// Largest accepted difference between the `.info` start date and the first sample, in milliseconds.
+const START_DATE_TOLERANCE_MS: i64 = 1000;
+
+/// Largest accepted relative difference between the declared and the effective sampling rate.
+const SAMPLING_RATE_TOLERANCE: Float = 0.02;
+
+/// A disagreement between the `.info` metadata and the content of the `.easy` file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationWarning {
+    /// No `.info` file was loaded, nothing could be cross-checked.
+    MissingInfo,
+
+    /// The data has not been loaded with `parse_data`, only the layout could be checked.
+    DataNotLoaded,
+
+    /// `Number of EEG channels` or the number of channels of the montage does not match the
+    /// columns of the `.easy` file.
+    ChannelCount { declared: usize, found: usize },
+
+    /// `Number of records of EEG` does not match the rows of the `.easy` file.
+    RecordCount { declared: usize, found: usize },
+
+    /// `StartDate` is too far from the timestamp of the first row.
+    StartDate {
+        declared: DateTime<Utc>,
+        found: DateTime<Utc>,
+    },
+
+    /// The sampling rate computed from the timestamps differs from `EEG sampling rate`.
+    SamplingRate { declared: Float, effective: Float },
+
+    /// `Number of packets lost` does not match the samples missing from the timestamps.
+    PacketsLost {
+        declared: usize,
+        gaps: usize,
+        missing_samples: usize,
+    },
+
+    /// `Accelerometer data` does not match the presence of accelerometer columns.
+    Accelerometer { declared: bool, found: bool },
+}
+
+impl fmt::Display for ValidationWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationWarning::MissingInfo => write!(f, "no .info file to validate against"),
+            ValidationWarning::DataNotLoaded => write!(f, "data not loaded, call parse_data first"),
+            ValidationWarning::ChannelCount { declared, found } => write!(
+                f,
+                "{declared} EEG channels declared, {found} found in the data"
+            ),
+            ValidationWarning::RecordCount { declared, found } => {
+                write!(f, "{declared} records declared, {found} rows in the data")
+            }
+            ValidationWarning::StartDate { declared, found } => {
+                write!(f, "start date declared {declared}, first sample at {found}")
+            }
+            ValidationWarning::SamplingRate {
+                declared,
+                effective,
+            } => write!(
+                f,
+                "sampling rate declared {declared} Hz, effective {effective:.3} Hz"
+            ),
+            ValidationWarning::PacketsLost {
+                declared,
+                gaps,
+                missing_samples,
+            } => write!(
+                f,
+                "{declared} packets lost declared, {missing_samples} samples missing in {gaps} gaps"
+            ),
+            ValidationWarning::Accelerometer { declared, found } => write!(
+                f,
+                "accelerometer declared {}, {} in the data",
+                if *declared { "ON" } else { "OFF" },
+                if *found { "present" } else { "absent" }
+            ),
+        }
+    }
+}
+
+/// Result of [`EasyReader::validate`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    pub warnings: Vec<ValidationWarning>,
+}
+
+impl ValidationReport {
+    /// Returns `true` if the metadata and the data agree.
+    pub fn is_ok(&self) -> bool {
+        self.warnings.is_empty()
+    }
+}
+
+/// A discontinuity in the timestamps of a recording.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gap {
+    /// Index of the first sample after the gap.
+    pub sample_index: usize,
+    /// Time between the samples on both sides of the gap, in milliseconds.
+    pub duration_ms: i64,
+    /// Number of samples that would fit in the gap at the nominal sampling rate.
+    pub missing_samples: usize,
+}
+
+/// Finds the places where consecutive timestamps are further apart than the sampling period allows.
+///
+/// Timestamps have a resolution of one millisecond, so a step counts as a gap when it exceeds
+/// one and a half sampling periods and is at least one millisecond longer than a period.
+pub fn detect_gaps(timestamps: &[DateTime<Utc>], sampling_rate: Float) -> Vec<Gap> {
+    if sampling_rate <= 0.0 {
+        return Vec::new();
+    }
+    let period_ms = 1000.0 / sampling_rate;
+    let threshold_ms = (1.5 * period_ms).max(period_ms + 1.0);
+
+    timestamps
+        .windows(2)
+        .enumerate()
+        .filter_map(|(i, pair)| {
+            let duration_ms = (pair[1] - pair[0]).num_milliseconds();
+            if (duration_ms as Float) <= threshold_ms {
+                return None;
+            }
+            Some(Gap {
+                sample_index: i + 1,
+                duration_ms,
+                missing_samples: ((duration_ms as Float / period_ms).round() as usize)
+                    .saturating_sub(1),
+            })
+        })
+        .collect()
+}
+
+impl EasyReader {
+    /// Cross-checks the `.info` metadata against the content of the `.easy` file.
+    ///
+    /// Compares the number of EEG channels, the number of records, the start date, the
+    /// sampling rate, the number of lost packets and the accelerometer status. Each mismatch
+    /// is reported as a [`ValidationWarning`]; an empty report means the pair is consistent.
+    pub fn validate(&self) -> ValidationReport {
+        let mut warnings = Vec::new();
+
+        let Some(info) = self.info() else {
+            return ValidationReport {
+                warnings: vec![ValidationWarning::MissingInfo],
+            };
+        };
+        let settings = &info.eeg_settings;
+
+        // Both the channel count and the montage declare the channels
+        if let Some(found) = self.num_channels() {
+            let mut declared = vec![settings.eeg_channels, settings.montage.len()];
+            declared.dedup();
+            for declared in declared {
+                if declared != 0 && declared != found {
+                    warnings.push(ValidationWarning::ChannelCount { declared, found });
+                }
+            }
+        }
+
+        if self.layout().is_some() {
+            let declared = settings.accelerometer.is_some();
+            let found = self.has_accelerometer();
+            if declared != found {
+                warnings.push(ValidationWarning::Accelerometer { declared, found });
+            }
+        }
+
+        let Some(timestamps) = self.timestamps() else {
+            warnings.push(ValidationWarning::DataNotLoaded);
+            return ValidationReport { warnings };
+        };
+
+        if settings.records != 0 && settings.records != timestamps.len() {
+            warnings.push(ValidationWarning::RecordCount {
+                declared: settings.records,
+                found: timestamps.len(),
+            });
+        }
+
+        if let (Some(declared), Some(found)) = (info.device_info.start_date, timestamps.first()) {
+            if (*found - declared).num_milliseconds().abs() > START_DATE_TOLERANCE_MS {
+                warnings.push(ValidationWarning::StartDate {
+                    declared,
+                    found: *found,
+                });
+            }
+        }
+
+        if let Some(declared) = self.sampling_rate() {
+            let gaps = detect_gaps(timestamps, declared);
+            let missing_samples = gaps.iter().map(|gap| gap.missing_samples).sum();
+            if settings.packets_lost != missing_samples {
+                warnings.push(ValidationWarning::PacketsLost {
+                    declared: settings.packets_lost,
+                    gaps: gaps.len(),
+                    missing_samples,
+                });
+            }
+
+            // Compare over the time actually covered, lost samples included
+            if let (Some(first), Some(last)) = (timestamps.first(), timestamps.last()) {
+                let span = (*last - *first).num_milliseconds() as Float / 1000.0;
+                if span > 0.0 {
+                    let effective = (timestamps.len() + missing_samples - 1) as Float / span;
+                    if (effective - declared).abs() / declared > SAMPLING_RATE_TOLERANCE {
+                        warnings.push(ValidationWarning::SamplingRate {
+                            declared,
+                            effective,
+                        });
+                    }
+                }
+            }
+        }
+
+        ValidationReport { warnings }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::SampleRecording;
+
+    fn timestamps(millis: &[i64]) -> Vec<DateTime<Utc>> {
+        millis
+            .iter()
+            .map(|ms| DateTime::from_timestamp_millis(1609459200000 + ms).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_detect_gaps() {
+        let gaps = detect_gaps(&timestamps(&[0, 2, 4, 6, 14, 16, 18]), 500.0);
+        assert_eq!(
+            gaps,
+            vec![Gap {
+                sample_index: 4,
+                duration_ms: 8,
+                missing_samples: 3
+            }]
+        );
+        assert!(detect_gaps(&timestamps(&[0, 2, 4, 5, 8]), 500.0).is_empty());
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut reader = SampleRecording::new(500.0, 6)
+            .eeg(|i, c| if c == 0 { i as i64 } else { c as i64 })
+            .gap(3, 6)
+            .scale(1.0)
+            .step_details("StartDate: 1609459200000\n")
+            .settings(
+                "Number of EEG channels: 8\nNumber of records of EEG: 10\n\
+                 Number of packets lost: 0\nAccelerometer data: ON\n",
+            )
+            .open();
+        assert!(reader
+            .validate()
+            .warnings
+            .contains(&ValidationWarning::DataNotLoaded));
+
+        reader.parse_data().unwrap();
+        let report = reader.validate();
+        assert!(!report.is_ok());
+        assert_eq!(
+            report.warnings,
+            vec![
+                ValidationWarning::Accelerometer {
+                    declared: true,
+                    found: false
+                },
+                ValidationWarning::RecordCount {
+                    declared: 10,
+                    found: 6
+                },
+                ValidationWarning::PacketsLost {
+                    declared: 0,
+                    gaps: 1,
+                    missing_samples: 3
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_channel_count() {
+        // An .info file listing 4 channels, paired with a file of 8
+        let reader = SampleRecording::new(500.0, 6)
+            .settings("Number of EEG channels: 4\n")
+            .montage(&["Fp1", "Fp2", "F3", "F4"])
+            .reader();
+        assert_eq!(reader.num_channels(), Some(8));
+        assert_eq!(reader.electrodes()[7], "Ch8");
+        assert_eq!(
+            reader.validate().warnings,
+            vec![ValidationWarning::ChannelCount {
+                declared: 4,
+                found: 8
+            }]
+        );
+    }
+}